@@ -0,0 +1,386 @@
+//! Bodies stored column by column, one contiguous array per property, so that reading a property
+//! of every body walks memory in order and takes no locks.
+//!
+//! The simulation reads one [`Bodies`] while it writes the next step into another, then publishes
+//! that as the new snapshot. Readers hold on to whichever snapshot was current when they asked, so
+//! they never see a half-finished step and never hold up the simulation.
+
+use std::sync::Arc;
+
+use crate::{
+    boid::Boid, quadtree::Item, sph::Gas, traits::Mass, traits::Position, vector::Vector2,
+};
+
+/// Display radius of the heaviest body. Each factor of ten lighter is drawn a step smaller, down
+/// to one.
+const LARGEST_RADIUS: f64 = 30.0;
+
+#[derive(Debug, Default)]
+pub struct Bodies {
+    /// Stable identity of each body, which stays with it when the bodies are reordered.
+    ids: Vec<usize>,
+    positions: Vec<Vector2<f64>>,
+    velocities: Vec<Vector2<f64>>,
+    masses: Vec<f64>,
+    charges: Vec<f64>,
+    /// Strength of each body as a point vortex, positive anticlockwise.
+    circulations: Vec<f64>,
+    /// Hydrodynamic state, for bodies that are SPH gas particles.
+    gas: Vec<Option<Gas>>,
+    /// Gravitational acceleration on the previous step, for the relative opening criterion.
+    accelerations: Vec<Vector2<f64>>,
+    /// Each body's own opening angle when the tree walk adapts it, or zero before it has one.
+    opening_angles: Vec<f64>,
+}
+
+impl Bodies {
+    pub fn len(&self) -> usize {
+        self.masses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.masses.is_empty()
+    }
+
+    pub fn push(&mut self, boid: Boid) {
+        self.ids.push(self.ids.len());
+        self.positions.push(boid.position());
+        self.velocities.push(boid.velocity());
+        self.masses.push(boid.mass());
+        self.charges.push(boid.charge());
+        self.circulations.push(boid.circulation());
+        self.gas.push(boid.gas());
+        self.accelerations.push(Vector2::default());
+        self.opening_angles.push(0.0);
+    }
+
+    /// The body at `index`.
+    pub fn get(&self, index: usize) -> BodyRef<'_> {
+        BodyRef {
+            bodies: self,
+            index,
+        }
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = BodyRef<'_>> + Clone {
+        (0..self.len()).map(|index| self.get(index))
+    }
+
+    /// What the spatial indexes hold for every body, in order.
+    pub fn particles(&self) -> Vec<Particle> {
+        self.iter().map(BodyRef::particle).collect()
+    }
+
+    pub fn positions(&self) -> &[Vector2<f64>] {
+        &self.positions
+    }
+
+    pub fn velocities(&self) -> &[Vector2<f64>] {
+        &self.velocities
+    }
+
+    pub fn masses(&self) -> &[f64] {
+        &self.masses
+    }
+
+    pub fn charges(&self) -> &[f64] {
+        &self.charges
+    }
+
+    pub fn circulations(&self) -> &[f64] {
+        &self.circulations
+    }
+
+    /// Mass of the heaviest body, which display radii are measured against.
+    pub fn heaviest(&self) -> f64 {
+        self.masses.iter().copied().fold(0.0, f64::max)
+    }
+
+    pub fn set_position(&mut self, index: usize, position: Vector2<f64>) {
+        self.positions[index] = position;
+    }
+
+    pub fn set_velocity(&mut self, index: usize, velocity: Vector2<f64>) {
+        self.velocities[index] = velocity;
+    }
+
+    pub fn set_gas(&mut self, index: usize, gas: Option<Gas>) {
+        self.gas[index] = gas;
+    }
+
+    pub fn set_acceleration(&mut self, index: usize, acceleration: Vector2<f64>) {
+        self.accelerations[index] = acceleration;
+    }
+
+    pub fn set_opening_angle(&mut self, index: usize, opening_angle: f64) {
+        self.opening_angles[index] = opening_angle;
+    }
+
+    /// Reorders the bodies so that the one at `order[i]` ends up at `i`.
+    pub fn permute(&mut self, order: &[usize]) {
+        fn apply<T: Copy>(column: &mut Vec<T>, order: &[usize]) {
+            *column = order.iter().map(|&index| column[index]).collect();
+        }
+        apply(&mut self.ids, order);
+        apply(&mut self.positions, order);
+        apply(&mut self.velocities, order);
+        apply(&mut self.masses, order);
+        apply(&mut self.charges, order);
+        apply(&mut self.circulations, order);
+        apply(&mut self.gas, order);
+        apply(&mut self.accelerations, order);
+        apply(&mut self.opening_angles, order);
+    }
+}
+
+impl Clone for Bodies {
+    fn clone(&self) -> Self {
+        let mut bodies = Self::default();
+        bodies.clone_from(self);
+        bodies
+    }
+
+    /// Copies `source` into the columns' existing buffers, so that writing each step into the
+    /// previous snapshot does not allocate.
+    fn clone_from(&mut self, source: &Self) {
+        self.ids.clone_from(&source.ids);
+        self.positions.clone_from(&source.positions);
+        self.velocities.clone_from(&source.velocities);
+        self.masses.clone_from(&source.masses);
+        self.charges.clone_from(&source.charges);
+        self.circulations.clone_from(&source.circulations);
+        self.gas.clone_from(&source.gas);
+        self.accelerations.clone_from(&source.accelerations);
+        self.opening_angles.clone_from(&source.opening_angles);
+    }
+}
+
+/// A copy of `current` to write the next step into, reusing the buffers of `spare`, an older
+/// snapshot, once no reader still holds it.
+pub fn next_step(spare: Option<Arc<Bodies>>, current: &Bodies) -> Bodies {
+    match spare.map(Arc::try_unwrap) {
+        Some(Ok(mut bodies)) => {
+            bodies.clone_from(current);
+            bodies
+        }
+        _ => Bodies::clone(current),
+    }
+}
+
+impl FromIterator<Boid> for Bodies {
+    fn from_iter<I: IntoIterator<Item = Boid>>(boids: I) -> Self {
+        let mut bodies = Self::default();
+        for boid in boids {
+            bodies.push(boid);
+        }
+        bodies
+    }
+}
+
+/// One body in a [`Bodies`], read straight from its columns.
+#[derive(Debug, Clone, Copy)]
+pub struct BodyRef<'a> {
+    bodies: &'a Bodies,
+    index: usize,
+}
+
+impl BodyRef<'_> {
+    pub const fn index(self) -> usize {
+        self.index
+    }
+
+    /// Identifies the body for as long as it exists, unlike its index, which changes whenever the
+    /// bodies are put back into Morton order.
+    pub fn id(self) -> usize {
+        self.bodies.ids[self.index]
+    }
+
+    pub fn position(self) -> Vector2<f64> {
+        self.bodies.positions[self.index]
+    }
+
+    pub fn velocity(self) -> Vector2<f64> {
+        self.bodies.velocities[self.index]
+    }
+
+    pub fn mass(self) -> f64 {
+        self.bodies.masses[self.index]
+    }
+
+    pub fn charge(self) -> f64 {
+        self.bodies.charges[self.index]
+    }
+
+    pub fn circulation(self) -> f64 {
+        self.bodies.circulations[self.index]
+    }
+
+    pub fn gas(self) -> Option<Gas> {
+        self.bodies.gas[self.index]
+    }
+
+    pub fn acceleration(self) -> Vector2<f64> {
+        self.bodies.accelerations[self.index]
+    }
+
+    pub fn opening_angle(self) -> f64 {
+        self.bodies.opening_angles[self.index]
+    }
+
+    /// Display radius, from the mass as a fraction of `heaviest` so that it does not depend on
+    /// the scenario's units.
+    pub fn radius(self, heaviest: f64) -> f64 {
+        // Small masses would otherwise give a negative radius that the canvas refuses to draw.
+        (LARGEST_RADIUS + (self.mass() / heaviest).log10()).max(1.0)
+    }
+
+    pub fn particle(self) -> Particle {
+        Particle {
+            index: self.index,
+            position: self.position(),
+            mass: self.mass(),
+            charge: self.charge(),
+            circulation: self.circulation(),
+            acceleration: self.acceleration(),
+        }
+    }
+}
+
+impl Mass for BodyRef<'_> {
+    fn mass(&self) -> f64 {
+        Self::mass(*self)
+    }
+
+    fn center_of_mass(&self) -> Vector2<f64> {
+        self.position()
+    }
+}
+
+impl Position for BodyRef<'_> {
+    fn position(&self) -> Vector2<f64> {
+        Self::position(*self)
+    }
+}
+
+impl Item for BodyRef<'_> {
+    fn charge(&self) -> f64 {
+        Self::charge(*self)
+    }
+
+    fn circulation(&self) -> f64 {
+        Self::circulation(*self)
+    }
+
+    fn acceleration(&self) -> Vector2<f64> {
+        Self::acceleration(*self)
+    }
+}
+
+/// A copy of what the trees and grids need to know about a body, with its index to find the rest.
+/// Holding copies rather than borrowing the bodies lets a tree outlive the snapshot it was built
+/// from.
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    pub index: usize,
+    pub position: Vector2<f64>,
+    pub mass: f64,
+    pub charge: f64,
+    pub circulation: f64,
+    pub acceleration: Vector2<f64>,
+}
+
+impl Mass for Particle {
+    fn mass(&self) -> f64 {
+        self.mass
+    }
+
+    fn center_of_mass(&self) -> Vector2<f64> {
+        self.position
+    }
+}
+
+impl Position for Particle {
+    fn position(&self) -> Vector2<f64> {
+        self.position
+    }
+}
+
+impl Item for Particle {
+    fn charge(&self) -> f64 {
+        self.charge
+    }
+
+    fn circulation(&self) -> f64 {
+        self.circulation
+    }
+
+    fn acceleration(&self) -> Vector2<f64> {
+        self.acceleration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{boundary::Boundary, morton, random::Random};
+
+    fn scattered(count: usize, seed: u64) -> Bodies {
+        let mut random = Random::new(seed);
+        (0..count)
+            .map(|_| Boid::new(random.uniform(), random.uniform(), 1.0))
+            .collect()
+    }
+
+    /// Moves every body a little, as a step of the simulation would.
+    fn step(bodies: &mut Bodies) {
+        for index in 0..bodies.len() {
+            let position = bodies.get(index).position();
+            bodies.set_position(index, position + Vector2::new(0.01, 0.0));
+        }
+    }
+
+    #[test]
+    fn published_snapshots_stay_unchanged() {
+        let current = Arc::new(scattered(100, 1));
+        let spare = Arc::new(scattered(100, 2));
+        // A reader still holds the older snapshot, so the next step must not go into it.
+        let reader = Arc::clone(&spare);
+        let before = reader.positions().to_vec();
+        let mut next = next_step(Some(spare), &current);
+        step(&mut next);
+        assert_eq!(reader.positions(), &before[..]);
+        assert_eq!(current.positions(), scattered(100, 1).positions());
+
+        // Once it is let go, its buffers are written into instead.
+        let buffer = reader.positions().as_ptr();
+        let mut next = next_step(Some(reader), &current);
+        step(&mut next);
+        assert_eq!(next.positions().as_ptr(), buffer);
+        assert_eq!(current.positions(), scattered(100, 1).positions());
+    }
+
+    #[test]
+    fn ids_follow_their_bodies_across_ticks() {
+        let bounds = Boundary {
+            min: Vector2::new(0.0, 0.0),
+            max: Vector2::new(2.0, 2.0),
+        };
+        let start = scattered(100, 3);
+        let mut current = Arc::new(start.clone());
+        let mut spare = None;
+        for tick in 1..=5 {
+            let mut next = next_step(spare.take(), &current);
+            step(&mut next);
+            next.permute(&morton::order(next.positions(), bounds));
+            let moved = Vector2::new(0.01 * f64::from(tick), 0.0);
+            for body in next.iter() {
+                let expected = start.get(body.id()).position() + moved;
+                assert!(
+                    (body.position() - expected).magnitude() < 1e-12,
+                    "tick {tick}"
+                );
+            }
+            spare = Some(std::mem::replace(&mut current, Arc::new(next)));
+        }
+    }
+}
@@ -1,61 +1,79 @@
-use std::{fmt, sync::RwLock};
+use std::fmt;
 
-use crate::{traits::Mass, vector::Vector2};
+use crate::{
+    sph::Gas,
+    traits::{Mass, Position},
+    vector::Vector2,
+};
 
+/// The starting state of a body in a scenario, before it is added to the simulation's
+/// [`Bodies`](crate::bodies::Bodies).
 #[derive(Debug, Clone, Copy)]
-pub(crate) struct BoidInner {
-    pub pos: Vector2<f64>,
-    pub velocity: Vector2<f64>,
-    pub mass: f64,
+pub struct Boid {
+    pos: Vector2<f64>,
+    velocity: Vector2<f64>,
+    mass: f64,
+    charge: f64,
+    /// Strength of the body as a point vortex, positive anticlockwise.
+    circulation: f64,
+    /// Hydrodynamic state, for bodies that are SPH gas particles.
+    gas: Option<Gas>,
 }
 
-impl BoidInner {
+impl Boid {
     pub fn new(x: f64, y: f64, mass: f64) -> Self {
         Self {
             pos: Vector2::new(x, y),
             velocity: Vector2::default(),
             mass,
+            charge: 0.0,
+            circulation: 0.0,
+            gas: None,
         }
     }
-    pub fn radius(&self) -> f64 {
-        self.mass.log10()
+
+    pub const fn position(&self) -> Vector2<f64> {
+        self.pos
     }
-}
 
-#[derive(Debug)]
-pub struct Boid {
-    pub(crate) inner: RwLock<BoidInner>,
-}
+    pub const fn mass(&self) -> f64 {
+        self.mass
+    }
 
-impl Boid {
-    pub fn new(x: f64, y: f64, mass: f64) -> Self {
-        Self {
-            inner: RwLock::new(BoidInner::new(x, y, mass)),
-        }
+    pub const fn velocity(&self) -> Vector2<f64> {
+        self.velocity
+    }
+
+    pub const fn charge(&self) -> f64 {
+        self.charge
+    }
+
+    pub const fn circulation(&self) -> f64 {
+        self.circulation
     }
 
-    pub fn position(&self) -> Vector2<f64> {
-        self.inner.read().expect("RWLock was poisoned").pos
+    pub const fn gas(&self) -> Option<Gas> {
+        self.gas
     }
 
-    pub fn mass(&self) -> f64 {
-        self.inner.read().expect("RWLock was poisoned").mass
+    pub fn set_position(&mut self, position: Vector2<f64>) {
+        self.pos = position;
     }
 
-    pub fn velocity(&self) -> Vector2<f64> {
-        self.inner.read().expect("RWLock was poisoned").velocity
+    pub fn set_velocity(&mut self, velocity: Vector2<f64>) {
+        self.velocity = velocity;
     }
 
-    pub fn set_position(&self, position: Vector2<f64>) {
-        self.inner.write().expect("RWLock was poisoned").pos = position;
+    pub fn set_charge(&mut self, charge: f64) {
+        self.charge = charge;
     }
 
-    pub fn set_velocity(&self, velocity: Vector2<f64>) {
-        self.inner.write().expect("RWLock was poisoned").velocity = velocity;
+    pub fn set_circulation(&mut self, circulation: f64) {
+        self.circulation = circulation;
     }
 
-    pub fn radius(&self) -> f64 {
-        self.inner.read().expect("RWLock was poisoned").radius()
+    pub fn set_gas(&mut self, gas: Option<Gas>) {
+        self.gas = gas;
     }
 }
 
@@ -69,6 +87,12 @@ impl Mass for Boid {
     }
 }
 
+impl Position for Boid {
+    fn position(&self) -> Vector2<f64> {
+        self.position()
+    }
+}
+
 impl fmt::Display for Boid {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
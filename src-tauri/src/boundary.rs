@@ -1,6 +1,6 @@
-use crate::{boid::Boid, traits::Intersect, types::BoidRCell, vector::Vector2};
+use crate::{bodies::Particle, boid::Boid, traits::Intersect, vector::Vector2};
 
-#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct Boundary {
     pub min: Vector2<f64>,
     pub max: Vector2<f64>,
@@ -41,6 +41,13 @@ impl Boundary {
     pub fn half_size(&self) -> f64 {
         (self.max.x - self.min.x) / 2.0
     }
+
+    /// Distance from `point` to the nearest point of the boundary, or zero if it is inside.
+    pub fn distance_to(&self, point: Vector2<f64>) -> f64 {
+        let dx = (self.min.x - point.x).max(point.x - self.max.x).max(0.0);
+        let dy = (self.min.y - point.y).max(point.y - self.max.y).max(0.0);
+        dx.hypot(dy)
+    }
 }
 
 impl Intersect<Vector2<f64>> for Boundary {
@@ -61,9 +68,9 @@ impl Intersect<Self> for Boundary {
     }
 }
 
-impl Intersect<BoidRCell> for Boundary {
-    fn intersects(&self, other: &BoidRCell) -> bool {
-        self.intersects(&other.position())
+impl Intersect<Particle> for Boundary {
+    fn intersects(&self, other: &Particle) -> bool {
+        self.intersects(&other.position)
     }
 }
 impl Intersect<&Boid> for Boundary {
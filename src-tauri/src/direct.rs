@@ -0,0 +1,64 @@
+use crate::{
+    bodies::{Bodies, BodyRef},
+    force_law::ForceLaw,
+    vector::Vector2,
+    vortex::PointVortices,
+};
+
+/// Force on `body` from every other body in `bodies`, summed exactly pair by pair.
+///
+/// This costs `O(N)` per body, so it is only practical for small systems, but it is free of the
+/// approximation error of the tree walk.
+pub fn calculate_force<L: ForceLaw>(bodies: &Bodies, body: BodyRef, law: &L) -> Vector2<f64> {
+    calculate_force_between(bodies, body, law, bodies.masses())
+}
+
+/// Electrostatic force on `body`, where `law` is applied to charges rather than masses.
+pub fn calculate_electric_force<L: ForceLaw>(
+    bodies: &Bodies,
+    body: BodyRef,
+    law: &L,
+) -> Vector2<f64> {
+    calculate_force_between(bodies, body, law, bodies.charges())
+}
+
+/// Velocity induced at `body` by every other point vortex in `bodies`.
+pub fn calculate_induced_velocity(
+    bodies: &Bodies,
+    body: BodyRef,
+    kernel: PointVortices,
+) -> Vector2<f64> {
+    let position = body.position();
+    bodies
+        .positions()
+        .iter()
+        .zip(bodies.circulations())
+        .enumerate()
+        .filter(|&(index, _)| index != body.index())
+        .fold(
+            Vector2::default(),
+            |velocity, (_, (&other, &circulation))| {
+                velocity + kernel.induced_velocity(other - position, circulation)
+            },
+        )
+}
+
+/// Sums `law` over every other body, with each body's strength read from `strengths`.
+fn calculate_force_between<L: ForceLaw>(
+    bodies: &Bodies,
+    body: BodyRef,
+    law: &L,
+    strengths: &[f64],
+) -> Vector2<f64> {
+    let position = body.position();
+    let own_strength = strengths[body.index()];
+    bodies
+        .positions()
+        .iter()
+        .zip(strengths)
+        .enumerate()
+        .filter(|&(index, _)| index != body.index())
+        .fold(Vector2::default(), |force, (_, (&other, &strength))| {
+            force + law.force(other - position, own_strength, strength)
+        })
+}
@@ -0,0 +1,152 @@
+//! Complex numbers and fast Fourier transforms, for convolutions on a grid.
+
+use std::f64::consts::TAU;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
+
+use crate::vector::Vector2;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub const fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    /// `e^(i angle)`.
+    pub fn from_angle(angle: f64) -> Self {
+        Self::new(angle.cos(), angle.sin())
+    }
+
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    pub fn norm_sqr(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl AddAssign for Complex {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Neg for Complex {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::new(-self.re, -self.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+    fn div(self, other: Self) -> Self {
+        self * other.conj() * (1.0 / other.norm_sqr())
+    }
+}
+
+impl Mul<f64> for Complex {
+    type Output = Self;
+    fn mul(self, other: f64) -> Self {
+        Self::new(self.re * other, self.im * other)
+    }
+}
+
+impl From<Vector2<f64>> for Complex {
+    fn from(vector: Vector2<f64>) -> Self {
+        Self::new(vector.x, vector.y)
+    }
+}
+
+/// In-place iterative radix-2 transform. The length of `data` must be a power of two. The inverse
+/// is scaled by `1 / n`, so a round trip returns the input.
+pub fn fft(data: &mut [Complex], inverse: bool) {
+    let n = data.len();
+    assert!(n.is_power_of_two(), "FFT length {n} is not a power of two");
+
+    // Bit-reversal permutation.
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut length = 2;
+    while length <= n {
+        #[allow(clippy::cast_precision_loss)]
+        let step = Complex::from_angle(sign * TAU / length as f64);
+        for start in (0..n).step_by(length) {
+            let mut twiddle = Complex::new(1.0, 0.0);
+            for k in 0..length / 2 {
+                let even = data[start + k];
+                let odd = data[start + k + length / 2] * twiddle;
+                data[start + k] = even + odd;
+                data[start + k + length / 2] = even - odd;
+                twiddle = twiddle * step;
+            }
+        }
+        length <<= 1;
+    }
+
+    if inverse {
+        #[allow(clippy::cast_precision_loss)]
+        let scale = 1.0 / n as f64;
+        for value in data.iter_mut() {
+            *value = *value * scale;
+        }
+    }
+}
+
+/// Transforms a row-major `size` by `size` grid, rows then columns.
+pub fn fft_2d(data: &mut [Complex], size: usize, inverse: bool) {
+    for row in data.chunks_mut(size) {
+        fft(row, inverse);
+    }
+    let mut column = vec![Complex::default(); size];
+    for x in 0..size {
+        for y in 0..size {
+            column[y] = data[y * size + x];
+        }
+        fft(&mut column, inverse);
+        for y in 0..size {
+            data[y * size + x] = column[y];
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! External fields applied to charged bodies.
+
+use crate::vector::Vector2;
+
+/// Out-of-plane magnetic field, positive pointing towards the viewer.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum MagneticField {
+    Uniform {
+        strength: f64,
+    },
+    /// Varies linearly across the plane, `strength + gradient . position`, for grad-B drift.
+    Gradient {
+        strength: f64,
+        gradient: Vector2<f64>,
+    },
+}
+
+impl MagneticField {
+    /// The z component of the field at `position`.
+    pub fn at(&self, position: Vector2<f64>) -> f64 {
+        match self {
+            Self::Uniform { strength } => *strength,
+            Self::Gradient { strength, gradient } => strength + gradient.dot(&position),
+        }
+    }
+}
+
+/// In-plane electric field.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum ElectricField {
+    Uniform {
+        field: Vector2<f64>,
+    },
+    /// The radial part of a Penning trap's quadrupole, pushing positive charges away from `center`
+    /// in proportion to their distance from it.
+    Quadrupole {
+        strength: f64,
+        center: Vector2<f64>,
+    },
+}
+
+impl ElectricField {
+    pub fn at(&self, position: Vector2<f64>) -> Vector2<f64> {
+        match self {
+            Self::Uniform { field } => *field,
+            Self::Quadrupole { strength, center } => (position - *center) * *strength,
+        }
+    }
+}
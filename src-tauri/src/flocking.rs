@@ -0,0 +1,213 @@
+//! Reynolds' boids: steering rules that make bodies flock.
+
+use std::f64::consts::PI;
+
+use crate::{
+    bodies::BodyRef,
+    boid::Boid,
+    scenario::{Parameters, Scenario},
+    units::UnitSystem,
+    vector::Vector2,
+    DT,
+};
+
+/// Tuning for the three classic steering rules. Weights turn each rule into an acceleration, so
+/// they are in the scenario's units.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Flocking {
+    /// How far a boid can see its neighbours.
+    pub perception: f64,
+    /// Neighbours closer than this are pushed away.
+    pub separation_distance: f64,
+    /// Full angle of the cone in front of a boid in which it sees neighbours, in radians.
+    pub field_of_view: f64,
+    pub separation: f64,
+    pub alignment: f64,
+    pub cohesion: f64,
+    pub max_speed: f64,
+}
+
+impl Flocking {
+    /// Whether a boid at `position` heading along `velocity` can see a neighbour at `other`.
+    fn sees(&self, position: Vector2<f64>, velocity: Vector2<f64>, other: Vector2<f64>) -> bool {
+        let offset = other - position;
+        let distance = offset.magnitude();
+        if distance == 0.0 || distance > self.perception {
+            return false;
+        }
+        let speed = velocity.magnitude();
+        // A boid at rest has no heading, so it looks all around.
+        speed == 0.0
+            || velocity.dot(&offset) / (speed * distance) >= (self.field_of_view / 2.0).cos()
+    }
+
+    /// Steering acceleration on `body` from `candidates`, which must include every body within
+    /// [`Flocking::perception`] of it.
+    pub fn steering(&self, body: BodyRef, candidates: &[BodyRef]) -> Vector2<f64> {
+        let (position, velocity) = (body.position(), body.velocity());
+        let neighbours: Vec<_> = candidates
+            .iter()
+            .filter(|other| {
+                other.index() != body.index() && self.sees(position, velocity, other.position())
+            })
+            .collect();
+        if neighbours.is_empty() {
+            return Vector2::default();
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let count = neighbours.len() as f64;
+        let mut separation = Vector2::default();
+        let mut average_velocity = Vector2::default();
+        let mut average_position = Vector2::default();
+        for other in &neighbours {
+            let offset = position - other.position();
+            let distance = offset.magnitude();
+            if distance < self.separation_distance {
+                // Falls off with distance, so the closest neighbours dominate.
+                separation = separation + offset / (distance * distance);
+            }
+            average_velocity = average_velocity + other.velocity() / count;
+            average_position = average_position + other.position() / count;
+        }
+
+        separation * self.separation
+            + (average_velocity - velocity) * self.alignment
+            + (average_position - position) * self.cohesion
+    }
+
+    /// Scales `velocity` down to [`Flocking::max_speed`] if it is faster.
+    pub fn limit_speed(&self, velocity: Vector2<f64>) -> Vector2<f64> {
+        let speed = velocity.magnitude();
+        if speed > self.max_speed {
+            velocity * (self.max_speed / speed)
+        } else {
+            velocity
+        }
+    }
+}
+
+/// `count` boids scattered over a disc, heading every which way, with gravity switched off.
+/// Within a few seconds they gather into flocks.
+pub fn flock(count: u32) -> Scenario {
+    // The golden angle spreads positions and headings evenly without any randomness.
+    let golden_angle = PI * (3.0 - 5.0_f64.sqrt());
+    let bodies = (0..count)
+        .map(|i| {
+            let i = f64::from(i);
+            let radius = 150.0 * (i / f64::from(count)).sqrt();
+            let position = Vector2::new(radius, 0.0).rotate(i * golden_angle);
+            let mut boid = Boid::new(position.x, position.y, 1.0);
+            boid.set_velocity(Vector2::new(15.0, 0.0).rotate(i * golden_angle * 7.0));
+            boid
+        })
+        .collect();
+
+    Scenario {
+        bodies,
+        parameters: Parameters::new(UnitSystem::SI, DT)
+            .without_self_gravity()
+            .with_flocking(Flocking {
+                perception: 30.0,
+                separation_distance: 10.0,
+                field_of_view: 1.5 * PI,
+                separation: 100.0,
+                alignment: 1.0,
+                cohesion: 0.2,
+                max_speed: 25.0,
+            }),
+        period: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bodies::Bodies, quadtree::Quadtree, spatial::SpatialIndex};
+
+    /// Only the rule with the given weights in force.
+    fn rules(separation: f64, alignment: f64, cohesion: f64) -> Flocking {
+        Flocking {
+            perception: 30.0,
+            separation_distance: 10.0,
+            field_of_view: 1.5 * PI,
+            separation,
+            alignment,
+            cohesion,
+            max_speed: 25.0,
+        }
+    }
+
+    /// Steering on the first of `boids`, looking its flockmates up in a quadtree as the
+    /// simulation does.
+    fn steering(flocking: &Flocking, boids: Vec<Boid>) -> Vector2<f64> {
+        let bodies: Bodies = boids.into_iter().collect();
+        let tree = Quadtree::build(&bodies);
+        let body = bodies.get(0);
+        let flockmates: Vec<_> = tree
+            .query_radius(body.position(), flocking.perception)
+            .iter()
+            .map(|particle| bodies.get(particle.index))
+            .collect();
+        flocking.steering(body, &flockmates)
+    }
+
+    fn moving(x: f64, y: f64, velocity: Vector2<f64>) -> Boid {
+        let mut boid = Boid::new(x, y, 1.0);
+        boid.set_velocity(velocity);
+        boid
+    }
+
+    #[test]
+    fn separation_pushes_away_from_close_neighbours() {
+        let flocking = rules(100.0, 0.0, 0.0);
+        let boids = vec![
+            Boid::new(0.0, 0.0, 1.0),
+            Boid::new(2.0, 0.0, 1.0),
+            // Seen, but not close enough to push.
+            Boid::new(0.0, 20.0, 1.0),
+        ];
+        let steering = steering(&flocking, boids);
+        assert!(
+            (steering - Vector2::new(-50.0, 0.0)).magnitude() < 1e-12,
+            "{steering:?}"
+        );
+    }
+
+    #[test]
+    fn alignment_matches_the_neighbours_velocity() {
+        let flocking = rules(0.0, 1.0, 0.0);
+        let heading = Vector2::new(1.0, 0.0);
+        let boids = vec![
+            moving(0.0, 0.0, heading),
+            moving(20.0, 5.0, Vector2::new(0.0, 3.0)),
+            moving(20.0, -5.0, Vector2::new(0.0, 1.0)),
+            // Behind, in the blind spot.
+            moving(-20.0, 0.0, Vector2::new(0.0, -10.0)),
+            // Beyond the perception.
+            moving(40.0, 0.0, Vector2::new(0.0, -10.0)),
+        ];
+        let steering = steering(&flocking, boids);
+        assert!(
+            (steering - Vector2::new(-1.0, 2.0)).magnitude() < 1e-12,
+            "{steering:?}"
+        );
+    }
+
+    #[test]
+    fn cohesion_steers_towards_the_neighbours_centre() {
+        let flocking = rules(0.0, 0.0, 0.2);
+        let boids = vec![
+            Boid::new(0.0, 0.0, 1.0),
+            Boid::new(20.0, 0.0, 1.0),
+            Boid::new(0.0, 20.0, 1.0),
+            // Beyond the perception.
+            Boid::new(-50.0, -50.0, 1.0),
+        ];
+        let steering = steering(&flocking, boids);
+        assert!(
+            (steering - Vector2::new(2.0, 2.0)).magnitude() < 1e-12,
+            "{steering:?}"
+        );
+    }
+}
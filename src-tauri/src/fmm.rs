@@ -0,0 +1,366 @@
+//! The fast multipole method in two dimensions, using complex-variable expansions of the
+//! logarithmic potential (Greengard & Rokhlin 1987).
+//!
+//! Each body at `z_j` contributes `q_j log(z - z_j)` to a complex potential whose derivative, the
+//! field `E(z) = Σ q_j / (z - z_j)`, gives both logarithmic gravity and the Biot–Savart velocity of
+//! point vortices. Multipole expansions are built up the quadtree, converted into local expansions
+//! between well separated cells, and pushed back down to the bodies, so the cost is linear in the
+//! number of bodies for a fixed `order`.
+
+use crate::{
+    bodies::{BodyRef, Particle},
+    fft::Complex,
+    quadtree::{Node, Quadtree},
+    vector::Vector2,
+};
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+
+/// Two cells interact through their expansions when their radii add up to at most this fraction
+/// of the distance between their centres, so that truncation errors shrink geometrically with
+/// `order`.
+const SEPARATION: f64 = 0.5;
+
+#[derive(Debug)]
+struct Cell {
+    center: Complex,
+    /// Half the diagonal, so every body in the cell lies within it of `center`.
+    radius: f64,
+    /// The bodies in this cell are `bodies[start..end]`.
+    start: usize,
+    end: usize,
+    children: Vec<usize>,
+    /// Coefficients `a_0..=a_p` of `a_0 log(z - c) + Σ a_k / (z - c)^k`.
+    multipole: Vec<Complex>,
+}
+
+impl Cell {
+    const fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    const fn len(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+/// The field every body in a quadtree feels from all the others.
+pub struct Fmm {
+    /// Keyed by body index.
+    fields: HashMap<usize, Complex>,
+}
+
+impl Fmm {
+    /// Expands the field of `strength` (mass, circulation, …) to `order` terms. Nearby pairs are
+    /// summed directly with a Plummer-softened kernel of length `softening`.
+    pub fn new(
+        tree: &Quadtree,
+        strength: fn(&Particle) -> f64,
+        order: usize,
+        softening: f64,
+    ) -> Self {
+        let order = order.max(1);
+        let mut expansion = Expansion {
+            order,
+            softening,
+            binomials: binomials(2 * order),
+            cells: Vec::new(),
+            bodies: Vec::new(),
+        };
+        if tree.root().leaves().is_empty() && tree.root().children().next().is_none() {
+            return Self {
+                fields: HashMap::new(),
+            };
+        }
+        expansion.build(tree.root(), strength);
+
+        let mut locals = vec![vec![Complex::default(); order + 1]; expansion.cells.len()];
+        let mut fields = vec![Complex::default(); expansion.bodies.len()];
+        expansion.interact(0, 0, &mut locals, &mut fields);
+        expansion.descend(0, &mut locals, &mut fields);
+
+        Self {
+            fields: expansion
+                .bodies
+                .iter()
+                .zip(fields)
+                .map(|(body, field)| (body.index, field))
+                .collect(),
+        }
+    }
+
+    /// `Σ q_j / (z - z_j)` over every other body, or zero for a body that is not in the tree.
+    pub fn field(&self, body: BodyRef) -> Complex {
+        self.fields.get(&body.index()).copied().unwrap_or_default()
+    }
+
+    /// Logarithmic gravity on `body`, with `gravity` and `length` as in
+    /// [`crate::force_law::Logarithmic`].
+    pub fn gravity(&self, body: BodyRef, gravity: f64, length: f64) -> Vector2<f64> {
+        // Each source pulls with G m M / (r length) towards itself, which is -conj(M / (z - z_j)).
+        let field = self.field(body).conj() * (-gravity * body.mass() / length);
+        Vector2::new(field.re, field.im)
+    }
+
+    /// Velocity a point vortex picks up from all the others, whose strength is circulation.
+    pub fn induced_velocity(&self, body: BodyRef) -> Vector2<f64> {
+        // The complex velocity u - iv is E / (2πi).
+        let field = self.field(body);
+        Vector2::new(field.im, field.re) * (1.0 / TAU)
+    }
+}
+
+struct Source {
+    index: usize,
+    position: Complex,
+    strength: f64,
+}
+
+struct Expansion {
+    order: usize,
+    softening: f64,
+    binomials: Vec<Vec<f64>>,
+    cells: Vec<Cell>,
+    bodies: Vec<Source>,
+}
+
+impl Expansion {
+    /// Copies the non-empty nodes under `node` into `cells`, depth first, forming each multipole
+    /// from the body of a leaf (P2M) or by shifting those of the children (M2M).
+    fn build(&mut self, node: &Node, strength: fn(&Particle) -> f64) -> usize {
+        let boundary = node.boundary();
+        let center = Complex::new(
+            f64::midpoint(boundary.min.x, boundary.max.x),
+            f64::midpoint(boundary.min.y, boundary.max.y),
+        );
+        let index = self.cells.len();
+        self.cells.push(Cell {
+            center,
+            radius: 0.5 * (boundary.max - boundary.min).magnitude(),
+            start: self.bodies.len(),
+            end: self.bodies.len(),
+            children: Vec::new(),
+            multipole: vec![Complex::default(); self.order + 1],
+        });
+
+        let mut multipole = vec![Complex::default(); self.order + 1];
+        for body in node.leaves() {
+            let source = Source {
+                index: body.index,
+                position: body.position.into(),
+                strength: strength(body),
+            };
+            multipole[0] += Complex::new(source.strength, 0.0);
+            let offset = source.position - center;
+            let mut power = Complex::new(1.0, 0.0);
+            for (k, coefficient) in multipole.iter_mut().enumerate().skip(1) {
+                power = power * offset;
+                #[allow(clippy::cast_precision_loss)]
+                let k = k as f64;
+                *coefficient += power * (-source.strength / k);
+            }
+            self.bodies.push(source);
+        }
+        for child in node.children() {
+            let child = self.build(child, strength);
+            self.cells[index].children.push(child);
+            let shifted = self.shift_multipole(
+                &self.cells[child].multipole,
+                self.cells[child].center - center,
+            );
+            for (total, term) in multipole.iter_mut().zip(shifted) {
+                *total += term;
+            }
+        }
+        self.cells[index].end = self.bodies.len();
+        self.cells[index].multipole = multipole;
+        index
+    }
+
+    /// M2M: re-expands a multipole about a centre `offset` away from the original one.
+    fn shift_multipole(&self, multipole: &[Complex], offset: Complex) -> Vec<Complex> {
+        let mut powers = vec![Complex::new(1.0, 0.0); self.order + 1];
+        for k in 1..=self.order {
+            powers[k] = powers[k - 1] * offset;
+        }
+        let mut shifted = vec![Complex::default(); self.order + 1];
+        shifted[0] = multipole[0];
+        for l in 1..=self.order {
+            #[allow(clippy::cast_precision_loss)]
+            let mut term = powers[l] * multipole[0] * (-1.0 / l as f64);
+            for k in 1..=l {
+                term += multipole[k] * powers[l - k] * self.binomials[l - 1][k - 1];
+            }
+            shifted[l] = term;
+        }
+        shifted
+    }
+
+    /// M2L: turns the multipole of `source` into a local expansion about the centre of `target`.
+    fn multipole_to_local(&self, source: &Cell, target: &Cell, local: &mut [Complex]) {
+        let separation = source.center - target.center;
+        let inverse = Complex::new(1.0, 0.0) / separation;
+        let mut inverse_powers = vec![Complex::new(1.0, 0.0); self.order + 1];
+        for k in 1..=self.order {
+            inverse_powers[k] = inverse_powers[k - 1] * inverse;
+        }
+        let multipole = &source.multipole;
+        // The constant term is left out, since only the field is wanted.
+        for l in 1..=self.order {
+            #[allow(clippy::cast_precision_loss)]
+            let mut sum = multipole[0] * (-1.0 / l as f64);
+            for k in 1..=self.order {
+                let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+                sum += multipole[k] * inverse_powers[k] * (sign * self.binomials[l + k - 1][k - 1]);
+            }
+            local[l] += sum * inverse_powers[l];
+        }
+    }
+
+    /// Accumulates the influence of every body in `source` on every body in `target`, splitting
+    /// cells until they are well separated or small enough to sum directly.
+    fn interact(
+        &self,
+        target: usize,
+        source: usize,
+        locals: &mut [Vec<Complex>],
+        fields: &mut [Complex],
+    ) {
+        let (t, s) = (&self.cells[target], &self.cells[source]);
+        if target == source {
+            if t.len() * t.len() <= self.order * self.order {
+                self.direct(t, t, fields);
+                return;
+            }
+            for &a in &t.children {
+                for &b in &t.children {
+                    self.interact(a, b, locals, fields);
+                }
+            }
+            return;
+        }
+        let distance = (t.center - s.center).norm_sqr().sqrt();
+        if t.len() * s.len() <= self.order * self.order || t.is_leaf() && s.is_leaf() {
+            self.direct(t, s, fields);
+        } else if t.radius + s.radius <= SEPARATION * distance {
+            self.multipole_to_local(s, t, &mut locals[target]);
+        } else if s.is_leaf() || !t.is_leaf() && t.radius >= s.radius {
+            for &child in &t.children {
+                self.interact(child, source, locals, fields);
+            }
+        } else {
+            for &child in &s.children {
+                self.interact(target, child, locals, fields);
+            }
+        }
+    }
+
+    /// P2P: sums the softened kernel over every pair of bodies.
+    fn direct(&self, target: &Cell, source: &Cell, fields: &mut [Complex]) {
+        let softening = self.softening * self.softening;
+        for (field, body) in fields[target.start..target.end]
+            .iter_mut()
+            .zip(&self.bodies[target.start..target.end])
+        {
+            for other in &self.bodies[source.start..source.end] {
+                let offset = body.position - other.position;
+                let denominator = offset.norm_sqr() + softening;
+                if denominator > 0.0 {
+                    *field += offset.conj() * (other.strength / denominator);
+                }
+            }
+        }
+    }
+
+    /// L2L down to the leaves, then L2P at each body.
+    fn descend(&self, index: usize, locals: &mut [Vec<Complex>], fields: &mut [Complex]) {
+        let cell = &self.cells[index];
+        let local = locals[index].clone();
+        for &child in &cell.children {
+            let offset = cell.center - self.cells[child].center;
+            for (j, shifted) in locals[child].iter_mut().enumerate().skip(1) {
+                let mut power = Complex::new(1.0, 0.0);
+                for (l, coefficient) in local.iter().enumerate().skip(j) {
+                    *shifted += *coefficient * power * self.binomials[l][j];
+                    power = power * -offset;
+                }
+            }
+            self.descend(child, locals, fields);
+        }
+        if cell.is_leaf() {
+            for (field, body) in fields[cell.start..cell.end]
+                .iter_mut()
+                .zip(&self.bodies[cell.start..cell.end])
+            {
+                let offset = body.position - cell.center;
+                let mut power = Complex::new(1.0, 0.0);
+                for (l, coefficient) in local.iter().enumerate().skip(1) {
+                    #[allow(clippy::cast_precision_loss)]
+                    let l = l as f64;
+                    *field += *coefficient * power * l;
+                    power = power * offset;
+                }
+            }
+        }
+    }
+}
+
+/// Pascal's triangle up to row `size`.
+fn binomials(size: usize) -> Vec<Vec<f64>> {
+    let mut rows: Vec<Vec<f64>> = Vec::with_capacity(size + 1);
+    for n in 0..=size {
+        let mut row = vec![1.0; n + 1];
+        for k in 1..n {
+            row[k] = rows[n - 1][k - 1] + rows[n - 1][k];
+        }
+        rows.push(row);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bodies::Bodies, boid::Boid, random::Random, spatial::SpatialIndex};
+
+    #[test]
+    fn matches_direct_summation() {
+        let mut random = Random::new(3);
+        let bodies: Bodies = (0..500)
+            .map(|_| {
+                Boid::new(
+                    random.uniform() * 1000.0,
+                    random.uniform() * 1000.0,
+                    1.0 + random.uniform() * 9.0,
+                )
+            })
+            .collect();
+        let tree = Quadtree::build(&bodies);
+        let exact: Vec<Complex> = bodies
+            .iter()
+            .map(|body| {
+                bodies
+                    .iter()
+                    .filter(|other| other.index() != body.index())
+                    .fold(Complex::default(), |field, other| {
+                        let offset = Complex::from(body.position() - other.position());
+                        field + offset.conj() * (other.mass() / offset.norm_sqr())
+                    })
+            })
+            .collect();
+
+        // The truncation error shrinks geometrically with the order, by at least SEPARATION a term.
+        for (order, tolerance) in [(4, 1e-2), (8, 2e-4), (16, 1e-8)] {
+            let fmm = Fmm::new(&tree, |particle| particle.mass, order, 0.0);
+            let worst = bodies
+                .iter()
+                .map(|body| {
+                    let field = fmm.field(body);
+                    let exact = exact[body.index()];
+                    (field - exact).norm_sqr().sqrt() / exact.norm_sqr().sqrt()
+                })
+                .fold(0.0, f64::max);
+            assert!(worst < tolerance, "order {order}: {worst}");
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! Pairwise central force laws shared by the tree walk and direct summation.
+
+use crate::vector::Vector2;
+
+pub trait ForceLaw {
+    /// Magnitude of the attraction between a body of `mass` and a source of `source_mass` a
+    /// `distance` apart. Negative values repel.
+    fn magnitude(&self, distance: f64, mass: f64, source_mass: f64) -> f64;
+
+    /// Force on a body of `mass` from a source of `source_mass` at `displacement` from it.
+    fn force(&self, displacement: Vector2<f64>, mass: f64, source_mass: f64) -> Vector2<f64> {
+        let distance = displacement.magnitude();
+        if distance == 0.0 {
+            return Vector2::default();
+        }
+        displacement * (self.magnitude(distance, mass, source_mass) / distance)
+    }
+
+    /// Correction to the force on a body of `mass` from a source group whose centre of mass is at
+    /// `displacement` from it, given the group's second moments of mass about that centre as
+    /// `[xx, xy, yy]`.
+    ///
+    /// This is the next term after the monopole in the expansion of the group's field. It is exact
+    /// for any central law whose magnitude is linear in the source mass, with the law's first two
+    /// derivatives taken numerically.
+    fn quadrupole_force(
+        &self,
+        displacement: Vector2<f64>,
+        mass: f64,
+        moment: [f64; 3],
+    ) -> Vector2<f64> {
+        let distance = displacement.magnitude();
+        if distance == 0.0 {
+            return Vector2::default();
+        }
+        let step = distance * 1e-3;
+        let f = |r: f64| self.magnitude(r, mass, 1.0);
+        let (below, at, above) = (f(distance - step), f(distance), f(distance + step));
+        let slope = (above - below) / (2.0 * step);
+        let curvature = (above - 2.0 * at + below) / (step * step);
+
+        // Work from the group out to the body.
+        let direction = displacement / -distance;
+        let [xx, xy, yy] = moment;
+        let moment_direction = Vector2::new(
+            xx * direction.x + xy * direction.y,
+            xy * direction.x + yy * direction.y,
+        );
+        let projected = direction.dot(&moment_direction);
+        let trace = xx + yy;
+
+        // The potential's Hessian is `g u u^T + h I` along the unit vector `u`.
+        let g = slope - at / distance;
+        let g_slope = curvature - slope / distance + at / (distance * distance);
+        let h_slope = slope / distance - at / (distance * distance);
+        let radial = g_slope * projected - 2.0 * g * projected / distance + h_slope * trace;
+        (direction * radial + moment_direction * (2.0 * g / distance)) * -0.5
+    }
+}
+
+/// How many terms of the multipole expansion the tree uses for far away nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum ExpansionOrder {
+    /// Only the total mass at the centre of mass.
+    Monopole,
+    /// Adds the quadrupole, which captures how the mass is spread around the centre.
+    Quadrupole,
+}
+
+impl ExpansionOrder {
+    /// Power of `size / distance` that the first term left out scales with.
+    pub const fn truncation_power(self) -> i32 {
+        match self {
+            Self::Monopole => 2,
+            Self::Quadrupole => 3,
+        }
+    }
+}
+
+/// Inverse-square gravity.
+#[derive(Debug, Clone, Copy)]
+pub struct Newtonian {
+    pub gravity: f64,
+}
+
+impl ForceLaw for Newtonian {
+    fn magnitude(&self, distance: f64, mass: f64, source_mass: f64) -> f64 {
+        self.gravity * mass * source_mass / (distance * distance)
+    }
+}
+
+/// Gravity as it would be in a two-dimensional universe, from a logarithmic potential, so the
+/// force falls off as `1 / r`. It matches Newtonian gravity at `length`.
+#[derive(Debug, Clone, Copy)]
+pub struct Logarithmic {
+    pub gravity: f64,
+    pub length: f64,
+}
+
+impl ForceLaw for Logarithmic {
+    fn magnitude(&self, distance: f64, mass: f64, source_mass: f64) -> f64 {
+        self.gravity * mass * source_mass / (distance * self.length)
+    }
+}
+
+/// Screened gravity from a Yukawa potential `-G m M exp(-r / length) / r`.
+#[derive(Debug, Clone, Copy)]
+pub struct Yukawa {
+    pub gravity: f64,
+    pub length: f64,
+}
+
+impl ForceLaw for Yukawa {
+    fn magnitude(&self, distance: f64, mass: f64, source_mass: f64) -> f64 {
+        let scaled = distance / self.length;
+        self.gravity * mass * source_mass * (1.0 + scaled) * (-scaled).exp() / (distance * distance)
+    }
+}
+
+/// Modified Newtonian dynamics with the "simple" interpolating function. Below `acceleration` the
+/// Newtonian pull of each source is boosted towards `sqrt(G M a0) / r`.
+///
+/// MOND is not linear in the sources, so applying it pair by pair, or to tree nodes, is only an
+/// approximation, and the forces are not equal and opposite when the masses differ.
+#[derive(Debug, Clone, Copy)]
+pub struct Mond {
+    pub gravity: f64,
+    pub acceleration: f64,
+}
+
+impl ForceLaw for Mond {
+    fn magnitude(&self, distance: f64, mass: f64, source_mass: f64) -> f64 {
+        let newtonian = self.gravity * source_mass / (distance * distance);
+        if newtonian == 0.0 {
+            // The boost grows without bound as the pull vanishes, but never enough to make it
+            // anything but zero.
+            return 0.0;
+        }
+        let ratio = newtonian / self.acceleration;
+        mass * newtonian * (0.5 + (0.25 + 1.0 / ratio).sqrt())
+    }
+}
+
+/// Softened Coulomb interaction between charges, so like charges repel. The `mass` arguments of
+/// [`ForceLaw`] are the two charges.
+///
+/// The softening length keeps opposite charges from accelerating without bound as they meet.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Coulomb {
+    /// Coulomb's constant in the scenario's units.
+    pub constant: f64,
+    pub softening: f64,
+}
+
+impl ForceLaw for Coulomb {
+    fn magnitude(&self, distance: f64, charge: f64, source_charge: f64) -> f64 {
+        let softened = distance * distance + self.softening * self.softening;
+        -self.constant * charge * source_charge * distance / (softened * softened.sqrt())
+    }
+}
+
+/// Plummer softening of another law, which is evaluated at `sqrt(r^2 + length^2)` rather than `r`
+/// so that close encounters stay finite.
+#[derive(Debug, Clone, Copy)]
+pub struct Softened<L> {
+    pub law: L,
+    pub length: f64,
+}
+
+impl<L: ForceLaw> ForceLaw for Softened<L> {
+    fn magnitude(&self, distance: f64, mass: f64, source_mass: f64) -> f64 {
+        let softened = distance.hypot(self.length);
+        self.law.magnitude(softened, mass, source_mass) * distance / softened
+    }
+}
+
+/// Which force law a scenario runs with, minus the gravitational constant that comes from its
+/// units.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum ForceLawKind {
+    Newtonian,
+    Logarithmic { length: f64 },
+    Yukawa { length: f64 },
+    Mond { acceleration: f64 },
+}
+
+impl ForceLawKind {
+    pub fn with_gravity(self, gravity: f64) -> Law {
+        match self {
+            Self::Newtonian => Law::Newtonian(Newtonian { gravity }),
+            Self::Logarithmic { length } => Law::Logarithmic(Logarithmic { gravity, length }),
+            Self::Yukawa { length } => Law::Yukawa(Yukawa { gravity, length }),
+            Self::Mond { acceleration } => Law::Mond(Mond {
+                gravity,
+                acceleration,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Law {
+    Newtonian(Newtonian),
+    Logarithmic(Logarithmic),
+    Yukawa(Yukawa),
+    Mond(Mond),
+}
+
+impl ForceLaw for Law {
+    fn magnitude(&self, distance: f64, mass: f64, source_mass: f64) -> f64 {
+        match self {
+            Self::Newtonian(law) => law.magnitude(distance, mass, source_mass),
+            Self::Logarithmic(law) => law.magnitude(distance, mass, source_mass),
+            Self::Yukawa(law) => law.magnitude(distance, mass, source_mass),
+            Self::Mond(law) => law.magnitude(distance, mass, source_mass),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::TAU;
+
+    #[test]
+    fn mond_vanishes_without_a_pull() {
+        let mond = Mond {
+            gravity: 1.0,
+            acceleration: 1e-3,
+        };
+        assert!(mond.magnitude(1.0, 1.0, 0.0).abs() < f64::EPSILON);
+        assert!(mond.magnitude(f64::INFINITY, 1.0, 1.0).abs() < f64::EPSILON);
+        // Deep in the MOND regime the pull falls off as sqrt(G M a0) / r.
+        let deep = mond.magnitude(1e6, 1.0, 1.0);
+        assert!((deep * 1e6 / 1e-3_f64.sqrt() - 1.0).abs() < 1e-3, "{deep}");
+    }
+
+    /// Largest relative error of the monopole and of the monopole with the quadrupole added, for
+    /// a lopsided cluster of bodies about the origin seen from `distance` away in every direction.
+    fn expansion_errors<L: ForceLaw>(law: &L, distance: f64) -> (f64, f64) {
+        let sources = [
+            (Vector2::new(0.3, 0.1), 2.0),
+            (Vector2::new(-0.5, 0.4), 1.0),
+            (Vector2::new(0.1, -0.7), 3.0),
+            (Vector2::new(0.6, 0.6), 0.5),
+        ];
+        let mass: f64 = sources.iter().map(|(_, mass)| mass).sum();
+        let center = sources
+            .iter()
+            .fold(Vector2::default(), |sum, &(position, mass)| {
+                sum + position * mass
+            })
+            / mass;
+        let moment = sources
+            .iter()
+            .fold([0.0; 3], |[xx, xy, yy], &(position, mass)| {
+                let offset = position - center;
+                [
+                    xx + mass * offset.x * offset.x,
+                    xy + mass * offset.x * offset.y,
+                    yy + mass * offset.y * offset.y,
+                ]
+            });
+        let (mut monopole_error, mut quadrupole_error) = (0.0_f64, 0.0_f64);
+        for step in 0..16 {
+            let body = Vector2::new(distance, 0.0).rotate(f64::from(step) * TAU / 16.0);
+            let exact =
+                sources
+                    .iter()
+                    .fold(Vector2::default(), |force, &(position, source_mass)| {
+                        force + law.force(position - body, 1.0, source_mass)
+                    });
+            let monopole = law.force(center - body, 1.0, mass);
+            let quadrupole = monopole + law.quadrupole_force(center - body, 1.0, moment);
+            let error = |force: Vector2<f64>| (force - exact).magnitude() / exact.magnitude();
+            monopole_error = monopole_error.max(error(monopole));
+            quadrupole_error = quadrupole_error.max(error(quadrupole));
+        }
+        (monopole_error, quadrupole_error)
+    }
+
+    #[test]
+    fn quadrupole_beats_monopole() {
+        for distance in [4.0, 8.0, 16.0] {
+            for (name, (monopole, quadrupole)) in [
+                (
+                    "Newtonian",
+                    expansion_errors(&Newtonian { gravity: 1.0 }, distance),
+                ),
+                (
+                    "logarithmic",
+                    expansion_errors(
+                        &Logarithmic {
+                            gravity: 1.0,
+                            length: 0.5,
+                        },
+                        distance,
+                    ),
+                ),
+            ] {
+                // The quadrupole takes out the leading error, leaving one smaller by about another
+                // factor of the cluster's size over the distance.
+                assert!(
+                    quadrupole < monopole * 2.0 / distance,
+                    "{name} at {distance}: {monopole} against {quadrupole}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! A uniform grid of square cells, hashed by their coordinates so that only occupied cells take
+//! up memory. Neighbours within about a cell of a point are found by looking in a handful of
+//! cells, however many bodies there are.
+
+use std::collections::HashMap;
+
+use crate::{
+    bodies::{Bodies, BodyRef, Particle},
+    boundary::Boundary,
+    spatial::{self, MassGroup, Nearest, SpatialIndex},
+    traits::Intersect,
+    vector::Vector2,
+};
+
+/// Bodies per cell that [`SpatialIndex::build`] aims for.
+const BODIES_PER_CELL: f64 = 2.0;
+
+#[derive(Debug)]
+struct Cell {
+    bodies: Vec<Particle>,
+    mass: f64,
+    center_of_mass: Vector2<f64>,
+}
+
+#[derive(Debug)]
+pub struct UniformGrid {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Cell>,
+    /// Lowest and highest coordinates of the occupied cells.
+    occupied: ((i64, i64), (i64, i64)),
+}
+
+impl UniformGrid {
+    /// Sorts `bodies` into cells `cell_size` across. Searches out to about one cell are
+    /// quickest, so this is best set to the range of the interaction.
+    pub fn new(bodies: &Bodies, cell_size: f64) -> Self {
+        let mut grid = Self {
+            cell_size,
+            cells: HashMap::new(),
+            occupied: ((i64::MAX, i64::MAX), (i64::MIN, i64::MIN)),
+        };
+        let mut members: HashMap<(i64, i64), Vec<Particle>> = HashMap::new();
+        for body in bodies.iter().map(BodyRef::particle) {
+            let (i, j) = grid.coordinates(body.position);
+            let (low, high) = &mut grid.occupied;
+            *low = (low.0.min(i), low.1.min(j));
+            *high = (high.0.max(i), high.1.max(j));
+            members.entry((i, j)).or_default().push(body);
+        }
+        grid.cells = members
+            .into_iter()
+            .map(|(coordinates, bodies)| {
+                let (mass, center_of_mass) = spatial::aggregate(&bodies);
+                let cell = Cell {
+                    bodies,
+                    mass,
+                    center_of_mass,
+                };
+                (coordinates, cell)
+            })
+            .collect();
+        grid
+    }
+
+    fn coordinates(&self, position: Vector2<f64>) -> (i64, i64) {
+        #[allow(clippy::cast_possible_truncation)]
+        let cell = |value: f64| (value / self.cell_size).floor() as i64;
+        (cell(position.x), cell(position.y))
+    }
+
+    /// Calls `visit` on every occupied cell from `low` to `high`. A range wider than the grid's
+    /// occupied cells, such as a whole viewport, is found by going through those instead.
+    fn for_each_cell(&self, low: (i64, i64), high: (i64, i64), mut visit: impl FnMut(&Cell)) {
+        let width = |low: i64, high: i64| high.saturating_sub(low).saturating_add(1);
+        let span = width(low.0, high.0).saturating_mul(width(low.1, high.1));
+        if usize::try_from(span).map_or(true, |span| span > self.cells.len()) {
+            let inside =
+                |(i, j): (i64, i64)| (low.0..=high.0).contains(&i) && (low.1..=high.1).contains(&j);
+            for (&coordinates, cell) in &self.cells {
+                if inside(coordinates) {
+                    visit(cell);
+                }
+            }
+            return;
+        }
+        for i in low.0..=high.0 {
+            for j in low.1..=high.1 {
+                if let Some(cell) = self.cells.get(&(i, j)) {
+                    visit(cell);
+                }
+            }
+        }
+    }
+
+    fn bounds(&self, (i, j): (i64, i64)) -> Boundary {
+        #[allow(clippy::cast_precision_loss)]
+        let min = Vector2::new(i as f64, j as f64) * self.cell_size;
+        Boundary {
+            min,
+            max: min + Vector2::new(self.cell_size, self.cell_size),
+        }
+    }
+}
+
+impl SpatialIndex for UniformGrid {
+    fn build(bodies: &Bodies) -> Self {
+        let bounds = spatial::bounding_box(&bodies.particles());
+        let size = bounds.max - bounds.min;
+        #[allow(clippy::cast_precision_loss)]
+        let area_per_body = size.x.max(size.y).powi(2) / bodies.len().max(1) as f64;
+        let cell_size = (area_per_body * BODIES_PER_CELL).sqrt();
+        Self::new(bodies, if cell_size > 0.0 { cell_size } else { 1.0 })
+    }
+
+    fn query_rect(&self, rect: Boundary) -> Vec<Particle> {
+        let mut found = Vec::new();
+        self.for_each_cell(
+            self.coordinates(rect.min),
+            self.coordinates(rect.max),
+            |cell| {
+                found.extend(
+                    cell.bodies
+                        .iter()
+                        .filter(|body| rect.intersects(&body.position))
+                        .copied(),
+                );
+            },
+        );
+        found
+    }
+
+    fn query_radius(&self, center: Vector2<f64>, radius: f64) -> Vec<Particle> {
+        let (low, high) = (
+            self.coordinates(center - Vector2::new(radius, radius)),
+            self.coordinates(center + Vector2::new(radius, radius)),
+        );
+        let mut found = Vec::new();
+        self.for_each_cell(low, high, |cell| {
+            found.extend(
+                cell.bodies
+                    .iter()
+                    .filter(|body| (body.position - center).magnitude() <= radius)
+                    .copied(),
+            );
+        });
+        found
+    }
+
+    fn k_nearest(&self, point: Vector2<f64>, k: usize) -> Vec<Particle> {
+        if k == 0 || self.cells.is_empty() {
+            return Vec::new();
+        }
+        let (ci, cj) = self.coordinates(point);
+        let ((low_i, low_j), (high_i, high_j)) = self.occupied;
+        let mut nearest = Nearest::new(k);
+        let visit = |nearest: &mut Nearest, i: i64, j: i64| {
+            if let Some(cell) = self.cells.get(&(i, j)) {
+                for body in &cell.bodies {
+                    nearest.offer((body.position - point).magnitude(), body);
+                }
+            }
+        };
+        // Search rings of cells outwards, from the first that reaches the occupied cells to the
+        // last that still holds any of them, going only along the parts of each ring inside them.
+        let gap = |c: i64, low: i64, high: i64| low.saturating_sub(c).max(c.saturating_sub(high));
+        let first = gap(ci, low_i, high_i).max(gap(cj, low_j, high_j)).max(0);
+        let last = [
+            ci.saturating_sub(low_i),
+            high_i.saturating_sub(ci),
+            cj.saturating_sub(low_j),
+            high_j.saturating_sub(cj),
+        ]
+        .into_iter()
+        .fold(0, i64::max);
+        for ring in first..=last {
+            // The two sides of the ring along one axis, which are the same line for the centre.
+            let sides = |c: i64| {
+                [c.saturating_sub(ring), c.saturating_add(ring)]
+                    .into_iter()
+                    .take(if ring == 0 { 1 } else { 2 })
+            };
+            let across = |c: i64, low: i64, high: i64| {
+                c.saturating_sub(ring).max(low)..=c.saturating_add(ring).min(high)
+            };
+            // Top and bottom rows, corners included, then the columns between them.
+            for j in sides(cj).filter(|j| (low_j..=high_j).contains(j)) {
+                for i in across(ci, low_i, high_i) {
+                    visit(&mut nearest, i, j);
+                }
+            }
+            for i in sides(ci).filter(|i| (low_i..=high_i).contains(i)) {
+                for j in across(cj, low_j, high_j) {
+                    if j != cj.saturating_sub(ring) && j != cj.saturating_add(ring) {
+                        visit(&mut nearest, i, j);
+                    }
+                }
+            }
+            // Everything beyond ring `ring` is at least `ring` cells away, since the point lies
+            // somewhere in the centre cell.
+            #[allow(clippy::cast_precision_loss)]
+            let searched = ring as f64 * self.cell_size;
+            if nearest.reach() <= searched {
+                break;
+            }
+        }
+        nearest.into_bodies()
+    }
+
+    fn visit_mass(&self, open: &mut dyn FnMut(&MassGroup) -> bool) {
+        for (&coordinates, cell) in &self.cells {
+            let bounds = self.bounds(coordinates);
+            let group = MassGroup {
+                mass: cell.mass,
+                center_of_mass: cell.center_of_mass,
+                bounds,
+                body: match cell.bodies.as_slice() {
+                    [body] => Some(body),
+                    _ => None,
+                },
+            };
+            if open(&group) && group.body.is_none() {
+                for body in &cell.bodies {
+                    open(&MassGroup {
+                        mass: body.mass,
+                        center_of_mass: body.position,
+                        bounds: Boundary::from_center(body.position, 0.0),
+                        body: Some(body),
+                    });
+                }
+            }
+        }
+    }
+}
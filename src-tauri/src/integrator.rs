@@ -0,0 +1,120 @@
+use crate::vector::Vector2;
+
+/// How each tick advances a body's velocity and position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Integrator {
+    /// Kick then drift. Magnetic forces are applied explicitly, so gyration slowly gains energy.
+    SemiImplicitEuler,
+    /// The Boris pusher, which splits the kick in two around an exact rotation by the magnetic
+    /// field, so gyration conserves energy for any step size.
+    Boris,
+}
+
+impl Integrator {
+    /// Advances a body by `dt` under `acceleration` from everything but the magnetic field, and a
+    /// magnetic field giving the signed gyrofrequency `gyrofrequency` (`q B / m`).
+    ///
+    /// Returns the new position and velocity.
+    pub fn step(
+        self,
+        position: Vector2<f64>,
+        velocity: Vector2<f64>,
+        acceleration: Vector2<f64>,
+        gyrofrequency: f64,
+        dt: f64,
+    ) -> (Vector2<f64>, Vector2<f64>) {
+        let velocity = match self {
+            Self::SemiImplicitEuler => {
+                let lorentz = Vector2::new(velocity.y, -velocity.x) * gyrofrequency;
+                velocity + (acceleration + lorentz) * dt
+            }
+            Self::Boris => {
+                let half_kick = acceleration * (dt / 2.0);
+                let minus = velocity + half_kick;
+                let t = gyrofrequency * dt / 2.0;
+                let s = 2.0 * t / (1.0 + t * t);
+                let prime = minus + Vector2::new(minus.y, -minus.x) * t;
+                let plus = minus + Vector2::new(prime.y, -prime.x) * s;
+                plus + half_kick
+            }
+        };
+        (position + velocity * dt, velocity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::TAU;
+
+    use super::*;
+
+    /// Steps a body `steps` times with the Boris pusher from `velocity` at the origin.
+    fn push(
+        velocity: Vector2<f64>,
+        acceleration: Vector2<f64>,
+        gyrofrequency: f64,
+        dt: f64,
+        steps: usize,
+    ) -> (Vector2<f64>, Vector2<f64>) {
+        (0..steps).fold((Vector2::default(), velocity), |(position, velocity), _| {
+            Integrator::Boris.step(position, velocity, acceleration, gyrofrequency, dt)
+        })
+    }
+
+    #[test]
+    fn magnetic_field_conserves_kinetic_energy() {
+        let velocity = Vector2::new(3.0, 4.0);
+        // A coarse step, which would spiral outwards with the explicit kick.
+        let (_, end) = push(velocity, Vector2::default(), 2.0, 0.3, 100_000);
+        let (energy, start) = (end.dot(&end), velocity.dot(&velocity));
+        assert!(
+            (energy - start).abs() < 1e-10 * start,
+            "{energy} against {start}"
+        );
+    }
+
+    #[test]
+    fn gyration_has_the_larmor_period() {
+        let (charge, field, mass) = (2.0, 3.0, 1.5);
+        let period = TAU * mass / (charge * field);
+        let steps = 1_000;
+        #[allow(clippy::cast_precision_loss)]
+        let dt = period / steps as f64;
+        let velocity = Vector2::new(1.0, 0.0);
+        let (position, end) = push(
+            velocity,
+            Vector2::default(),
+            charge * field / mass,
+            dt,
+            steps,
+        );
+        assert!(position.magnitude() < 1e-4, "{position:?}");
+        assert!((end - velocity).magnitude() < 1e-4, "{end:?}");
+    }
+
+    #[test]
+    fn crossed_fields_drift_at_e_cross_b_over_b_squared() {
+        let (charge, mass, magnetic) = (1.0, 2.0, 4.0);
+        let electric = Vector2::new(0.0, 3.0);
+        // E × B / B² with B out of the plane.
+        let drift = Vector2::new(electric.y, -electric.x) / magnetic;
+        let gyrofrequency = charge * magnetic / mass;
+        // Ten gyroperiods of a thousand steps each.
+        let (time, steps) = (10.0 * TAU / gyrofrequency, 10_000);
+        #[allow(clippy::cast_precision_loss)]
+        let dt = time / steps as f64;
+        let (position, _) = push(
+            Vector2::default(),
+            electric * (charge / mass),
+            gyrofrequency,
+            dt,
+            steps,
+        );
+        // Starting from rest, the body traces whole cycloid arches over whole gyroperiods.
+        let velocity = position / time;
+        assert!(
+            (velocity - drift).magnitude() < 1e-3 * drift.magnitude(),
+            "{velocity:?} against {drift:?}"
+        );
+    }
+}
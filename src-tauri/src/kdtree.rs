@@ -0,0 +1,214 @@
+//! A k-d tree that splits its bodies in half at each level, across the wider side of their
+//! bounding box. It stays balanced however the bodies are clustered, unlike the quadtree, whose
+//! depth grows with how close the closest bodies are.
+
+use crate::{
+    bodies::{Bodies, Particle},
+    boundary::Boundary,
+    spatial::{self, MassGroup, Nearest, SpatialIndex},
+    traits::Intersect,
+    vector::Vector2,
+};
+
+/// Most bodies kept in a leaf.
+const LEAF_SIZE: usize = 8;
+
+#[derive(Debug)]
+struct KdNode {
+    /// The smallest box around the node's bodies.
+    bounds: Boundary,
+    mass: f64,
+    center_of_mass: Vector2<f64>,
+    /// The node's bodies are `bodies[start..end]`.
+    start: usize,
+    end: usize,
+    children: Option<(usize, usize)>,
+}
+
+#[derive(Debug)]
+pub struct KdTree {
+    nodes: Vec<KdNode>,
+    bodies: Vec<Particle>,
+}
+
+impl KdTree {
+    /// Adds the node for `bodies[start..end]`, reordering them so that each child's are
+    /// contiguous, and returns its index.
+    fn split(&mut self, start: usize, end: usize) -> usize {
+        let slice = &mut self.bodies[start..end];
+        let bounds = spatial::bounding_box(slice);
+        let (mass, center_of_mass) = spatial::aggregate(slice.iter());
+        let index = self.nodes.len();
+        self.nodes.push(KdNode {
+            bounds,
+            mass,
+            center_of_mass,
+            start,
+            end,
+            children: None,
+        });
+        if slice.len() > LEAF_SIZE {
+            let size = bounds.max - bounds.min;
+            let coordinate = |body: &Particle| {
+                let position = body.position;
+                if size.x >= size.y {
+                    position.x
+                } else {
+                    position.y
+                }
+            };
+            let middle = slice.len() / 2;
+            slice.select_nth_unstable_by(middle, |a, b| coordinate(a).total_cmp(&coordinate(b)));
+            let left = self.split(start, start + middle);
+            let right = self.split(start + middle, end);
+            self.nodes[index].children = Some((left, right));
+        }
+        index
+    }
+
+    fn query_rect_from(&self, node: usize, rect: Boundary, found: &mut Vec<Particle>) {
+        let node = &self.nodes[node];
+        if !node.bounds.intersects(&rect) {
+            return;
+        }
+        match node.children {
+            Some((left, right)) => {
+                self.query_rect_from(left, rect, found);
+                self.query_rect_from(right, rect, found);
+            }
+            None => found.extend(
+                self.bodies[node.start..node.end]
+                    .iter()
+                    .filter(|body| rect.intersects(&body.position))
+                    .copied(),
+            ),
+        }
+    }
+
+    fn query_radius_from(
+        &self,
+        node: usize,
+        center: Vector2<f64>,
+        radius: f64,
+        found: &mut Vec<Particle>,
+    ) {
+        let node = &self.nodes[node];
+        if node.bounds.distance_to(center) > radius {
+            return;
+        }
+        match node.children {
+            Some((left, right)) => {
+                self.query_radius_from(left, center, radius, found);
+                self.query_radius_from(right, center, radius, found);
+            }
+            None => found.extend(
+                self.bodies[node.start..node.end]
+                    .iter()
+                    .filter(|body| (body.position - center).magnitude() <= radius)
+                    .copied(),
+            ),
+        }
+    }
+
+    fn k_nearest_from(&self, node: usize, point: Vector2<f64>, nearest: &mut Nearest) {
+        let node = &self.nodes[node];
+        if node.bounds.distance_to(point) >= nearest.reach() {
+            return;
+        }
+        match node.children {
+            Some((left, right)) => {
+                // The nearer child first, so that the farther one is more likely to be skipped.
+                let (near, far) = if self.nodes[left].bounds.distance_to(point)
+                    <= self.nodes[right].bounds.distance_to(point)
+                {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+                self.k_nearest_from(near, point, nearest);
+                self.k_nearest_from(far, point, nearest);
+            }
+            None => {
+                for body in &self.bodies[node.start..node.end] {
+                    nearest.offer((body.position - point).magnitude(), body);
+                }
+            }
+        }
+    }
+
+    fn visit_mass_from(&self, node: usize, open: &mut dyn FnMut(&MassGroup) -> bool) {
+        let node = &self.nodes[node];
+        let bodies = &self.bodies[node.start..node.end];
+        let group = MassGroup {
+            mass: node.mass,
+            center_of_mass: node.center_of_mass,
+            bounds: node.bounds,
+            body: match bodies {
+                [body] => Some(body),
+                _ => None,
+            },
+        };
+        if !open(&group) || group.body.is_some() {
+            return;
+        }
+        match node.children {
+            Some((left, right)) => {
+                self.visit_mass_from(left, open);
+                self.visit_mass_from(right, open);
+            }
+            None => {
+                for body in bodies {
+                    open(&MassGroup {
+                        mass: body.mass,
+                        center_of_mass: body.position,
+                        bounds: Boundary::from_center(body.position, 0.0),
+                        body: Some(body),
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl SpatialIndex for KdTree {
+    fn build(bodies: &Bodies) -> Self {
+        let mut tree = Self {
+            nodes: Vec::new(),
+            bodies: bodies.particles(),
+        };
+        if !bodies.is_empty() {
+            tree.split(0, bodies.len());
+        }
+        tree
+    }
+
+    fn query_rect(&self, rect: Boundary) -> Vec<Particle> {
+        let mut found = Vec::new();
+        if !self.nodes.is_empty() {
+            self.query_rect_from(0, rect, &mut found);
+        }
+        found
+    }
+
+    fn query_radius(&self, center: Vector2<f64>, radius: f64) -> Vec<Particle> {
+        let mut found = Vec::new();
+        if !self.nodes.is_empty() {
+            self.query_radius_from(0, center, radius, &mut found);
+        }
+        found
+    }
+
+    fn k_nearest(&self, point: Vector2<f64>, k: usize) -> Vec<Particle> {
+        let mut nearest = Nearest::new(k);
+        if !self.nodes.is_empty() {
+            self.k_nearest_from(0, point, &mut nearest);
+        }
+        nearest.into_bodies()
+    }
+
+    fn visit_mass(&self, open: &mut dyn FnMut(&MassGroup) -> bool) {
+        if !self.nodes.is_empty() {
+            self.visit_mass_from(0, open);
+        }
+    }
+}
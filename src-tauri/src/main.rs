@@ -3,44 +3,176 @@
 #![allow(clippy::module_name_repetitions)]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::sync::{Arc, Mutex, RwLock};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
 use std::time::Duration;
 
+use bodies::{Bodies, BodyRef};
 use boid::Boid;
 use boundary::Boundary;
-use quadtree::Quadtree;
-use signals::{Body, TreeState};
+use field::{ElectricField, MagneticField};
+use flocking::Flocking;
+use fmm::Fmm;
+use force_law::{Coulomb, ExpansionOrder, ForceLawKind, Law};
+use grid::UniformGrid;
+use integrator::Integrator;
+use kdtree::KdTree;
+use mesh::ParticleMesh;
+use molecular::{LennardJones, Thermostat};
+use orbit::Attractor;
+use quadtree::{GroupForces, OpeningCriterion, Quadtree};
+use random::Random;
+use scenario::{InvalidSolver, Parameters, Preset, Scenario, Solver};
+use signals::{Body, Orbit, TreeState};
+use spatial::{NeighbourIndex, SpatialIndex};
+use sph::{Gas, Sph};
 use tauri::State;
-use types::BoidRCell;
+use units::UnitSystem;
 use vector::Vector2;
+use vortex::PointVortices;
 
+mod bodies;
 mod boid;
 mod boundary;
+mod direct;
+mod fft;
+mod field;
+mod flocking;
+mod fmm;
+mod force_law;
+mod grid;
+mod integrator;
+mod kdtree;
+mod mesh;
+mod molecular;
+mod morton;
+mod orbit;
+mod plasma;
 mod quadtree;
+mod random;
+mod scenario;
 mod signals;
-mod star_system;
+mod solar_system;
+mod spatial;
+mod sph;
+mod three_body;
 mod traits;
-mod types;
+mod units;
 mod vector;
+mod vortex;
 
 const TIMESTEP: u8 = 10;
 const DT: f64 = TIMESTEP as f64 / 1000.0;
-const MASS_ONE: f64 = 125e12;
-const MASS_TWO: f64 = 10e11;
-const SATELITE_MASS: f64 = 10e9;
 pub const GRAVITY: f64 = 6.67430e-11;
 const THETA: f64 = 0.9;
-const CENTER_X: f64 = 250.0;
-const CENTER_Y: f64 = 250.0;
+/// How much wider than the bodies a rebuilt tree is.
+const TREE_MARGIN: f64 = 1.1;
 
-static BOIDS: RwLock<Vec<BoidRCell>> = RwLock::new(Vec::new());
+/// The bodies as of the last tick. Each tick is written into a separate buffer and swapped in
+/// whole, so readers only hold the lock long enough to clone the [`Arc`].
+static BODIES: LazyLock<RwLock<Arc<Bodies>>> = LazyLock::new(RwLock::default);
 static TREE_STATE: RwLock<Option<TreeState>> = RwLock::new(Option::None);
+/// The indexes over [`BODIES`] as of the last tick, kept for the next one and for queries from the
+/// frontend. They are swapped together with [`BODIES`] while this lock is held, so reading both
+/// under it gives indexes and the bodies they were built over.
+static INDEXES: RwLock<Option<Arc<Indexes>>> = RwLock::new(Option::None);
+static PARAMETERS: RwLock<Parameters> = RwLock::new(Parameters::new(UnitSystem::SI, DT));
 static MIN: Mutex<Vector2<f64>> = Mutex::new(Vector2::new(0.0, 0.0));
 static MAX: Mutex<Vector2<f64>> = Mutex::new(Vector2::new(1000.0, 1000.0));
 
+/// The gravity tree over one snapshot of the bodies, and the index that short-range interactions
+/// search when it is not the tree.
+struct Indexes {
+    tree: Quadtree,
+    neighbours: Option<Box<dyn SpatialIndex + Send + Sync>>,
+}
+
+impl Indexes {
+    fn new(tree: Quadtree, bodies: &Bodies, parameters: &Parameters) -> Self {
+        Self {
+            neighbours: build_neighbour_index(bodies, parameters),
+            tree,
+        }
+    }
+
+    /// The index to search for bodies near a point, which queries from the frontend use too.
+    fn neighbours(&self) -> &dyn SpatialIndex {
+        self.neighbours.as_deref().map_or(&self.tree, |index| index)
+    }
+}
+
+/// The indexes as of the last tick and the bodies they were built over.
+fn snapshot(
+    indexes: &RwLock<Option<Arc<Indexes>>>,
+    bodies: &RwLock<Arc<Bodies>>,
+) -> (Option<Arc<Indexes>>, Arc<Bodies>) {
+    let indexes = indexes.read().unwrap();
+    let bodies = Arc::clone(&bodies.read().unwrap());
+    (indexes.clone(), bodies)
+}
+
+#[tauri::command]
+fn get_bodies(bodies: State<&'static RwLock<Arc<Bodies>>>) -> Vec<Body> {
+    let bodies = Arc::clone(&bodies.read().unwrap());
+    let heaviest = bodies.heaviest();
+    bodies
+        .iter()
+        .map(|body| Body::new(body, heaviest))
+        .collect()
+}
+
+/// Bodies inside `viewport`, so that the frontend only draws what can be seen.
+#[tauri::command]
+fn get_visible_bodies(
+    viewport: Boundary,
+    bodies: State<&'static RwLock<Arc<Bodies>>>,
+    indexes: State<&'static RwLock<Option<Arc<Indexes>>>>,
+) -> Vec<Body> {
+    let (indexes, bodies) = snapshot(&indexes, &bodies);
+    let heaviest = bodies.heaviest();
+    indexes
+        .map_or_else(Vec::new, |indexes| {
+            indexes.neighbours().query_rect(viewport)
+        })
+        .iter()
+        .map(|particle| Body::new(bodies.get(particle.index), heaviest))
+        .collect()
+}
+
+/// [`Body::id`] of the body nearest to `point`, if it is within `radius`.
+#[tauri::command]
+fn pick_body(
+    point: Vector2<f64>,
+    radius: f64,
+    bodies: State<&'static RwLock<Arc<Bodies>>>,
+    indexes: State<&'static RwLock<Option<Arc<Indexes>>>>,
+) -> Option<usize> {
+    let (indexes, bodies) = snapshot(&indexes, &bodies);
+    let nearest = indexes?.neighbours().k_nearest(point, 1).pop()?;
+    ((nearest.position - point).magnitude() <= radius).then(|| bodies.get(nearest.index).id())
+}
+
+/// Bodies within `radius` of `center`, such as those under a selection brush.
+#[tauri::command]
+fn get_bodies_near(
+    center: Vector2<f64>,
+    radius: f64,
+    bodies: State<&'static RwLock<Arc<Bodies>>>,
+    indexes: State<&'static RwLock<Option<Arc<Indexes>>>>,
+) -> Vec<Body> {
+    let (indexes, bodies) = snapshot(&indexes, &bodies);
+    let heaviest = bodies.heaviest();
+    indexes
+        .map_or_else(Vec::new, |indexes| {
+            indexes.neighbours().query_radius(center, radius)
+        })
+        .iter()
+        .map(|particle| Body::new(bodies.get(particle.index), heaviest))
+        .collect()
+}
+
 #[tauri::command]
-fn get_bodies(boids: State<&'static RwLock<Vec<BoidRCell>>>) -> Vec<Body> {
-    boids.read().unwrap().iter().map(Body::from).collect()
+fn get_units(parameters: State<&'static RwLock<Parameters>>) -> UnitSystem {
+    parameters.read().unwrap().units
 }
 
 #[tauri::command]
@@ -48,170 +180,837 @@ fn get_tree(tree_state: State<&'static RwLock<Option<TreeState>>>) -> Option<Tre
     tree_state.read().unwrap().clone()
 }
 
-fn cold_colapse(center: Vector2<f64>, radius: f64, count: u32) -> Vec<Arc<Boid>> {
-    let increment = (std::f64::consts::PI * 2.0) / f64::from(count);
-    let mut boids = vec![];
-    let mut theta = 0.0;
+#[tauri::command]
+fn get_orbits(
+    bodies: State<&'static RwLock<Arc<Bodies>>>,
+    parameters: State<&'static RwLock<Parameters>>,
+) -> Vec<Orbit> {
+    let gravity = parameters.read().unwrap().gravity();
+    let bodies = Arc::clone(&bodies.read().unwrap());
+    orbit::osculating_elements(&bodies, gravity)
+        .into_iter()
+        .enumerate()
+        .map(|(body, orbit)| Orbit {
+            body: bodies.get(body).id(),
+            attractor: match orbit.attractor {
+                Attractor::Body(primary) => Attractor::Body(bodies.get(primary).id()),
+                Attractor::Barycentre => Attractor::Barycentre,
+            },
+            elements: orbit.elements,
+            period: orbit
+                .elements
+                .and_then(|elements| elements.period(orbit.mu)),
+            periapsis: orbit.elements.map(|elements| elements.periapsis()),
+            apoapsis: orbit.elements.and_then(|elements| elements.apoapsis()),
+        })
+        .collect()
+}
 
-    while theta < std::f64::consts::PI * 2.0 {
-        let x = theta.sin() * radius + center.x;
-        let y = theta.cos() * radius + center.y;
-        boids.push(Arc::new(Boid::new(x, y, MASS_ONE)));
-        theta += increment;
-    }
-    boids
+#[tauri::command]
+fn set_force_law(force_law: ForceLawKind, parameters: State<&'static RwLock<Parameters>>) {
+    parameters.write().unwrap().force_law = force_law;
+}
+
+#[tauri::command]
+fn set_self_gravity(self_gravity: bool, parameters: State<&'static RwLock<Parameters>>) {
+    parameters.write().unwrap().self_gravity = self_gravity;
+}
+
+#[tauri::command]
+fn set_flocking(flocking: Option<Flocking>, parameters: State<&'static RwLock<Parameters>>) {
+    parameters.write().unwrap().flocking = flocking;
+}
+
+#[tauri::command]
+fn set_point_vortices(
+    point_vortices: Option<PointVortices>,
+    parameters: State<&'static RwLock<Parameters>>,
+) {
+    parameters.write().unwrap().point_vortices = point_vortices;
+}
+
+#[tauri::command]
+fn set_lennard_jones(
+    lennard_jones: Option<LennardJones>,
+    parameters: State<&'static RwLock<Parameters>>,
+) {
+    parameters.write().unwrap().lennard_jones = lennard_jones;
+}
+
+#[tauri::command]
+fn set_periodic_cell(
+    periodic_cell: Option<Boundary>,
+    parameters: State<&'static RwLock<Parameters>>,
+) {
+    parameters.write().unwrap().periodic_cell = periodic_cell;
 }
 
-fn orbital_speed(radius: f64, mass: f64) -> f64 {
-    (GRAVITY * mass / radius).sqrt()
+#[tauri::command]
+fn set_thermostat(thermostat: Option<Thermostat>, parameters: State<&'static RwLock<Parameters>>) {
+    parameters.write().unwrap().thermostat = thermostat;
+}
+
+/// The kinetic temperature `k_B T` of the bodies, in the scenario's units of energy.
+#[tauri::command]
+fn get_temperature(bodies: State<&'static RwLock<Arc<Bodies>>>) -> f64 {
+    let bodies = Arc::clone(&bodies.read().unwrap());
+    molecular::kinetic_temperature(bodies.velocities(), bodies.masses())
+}
+
+#[tauri::command]
+fn set_softening(softening: f64, parameters: State<&'static RwLock<Parameters>>) {
+    parameters.write().unwrap().softening = softening;
+}
+
+#[tauri::command]
+fn set_sph(sph: Option<Sph>, parameters: State<&'static RwLock<Parameters>>) {
+    parameters.write().unwrap().sph = sph;
+}
+
+#[tauri::command]
+fn set_electrostatics(
+    electrostatics: Option<Coulomb>,
+    parameters: State<&'static RwLock<Parameters>>,
+) {
+    parameters.write().unwrap().electrostatics = electrostatics;
+}
+
+#[tauri::command]
+fn set_group_size(group_size: Option<usize>, parameters: State<&'static RwLock<Parameters>>) {
+    parameters.write().unwrap().group_size = group_size;
+}
+
+#[tauri::command]
+fn set_neighbour_index(
+    neighbour_index: NeighbourIndex,
+    parameters: State<&'static RwLock<Parameters>>,
+) {
+    parameters.write().unwrap().neighbour_index = neighbour_index;
+}
+
+#[tauri::command]
+fn set_accuracy(accuracy: Option<f64>, parameters: State<&'static RwLock<Parameters>>) {
+    parameters.write().unwrap().accuracy = accuracy;
+}
+
+#[tauri::command]
+fn set_opening(opening: OpeningCriterion, parameters: State<&'static RwLock<Parameters>>) {
+    parameters.write().unwrap().opening = opening;
+}
+
+#[tauri::command]
+fn set_expansion(expansion: ExpansionOrder, parameters: State<&'static RwLock<Parameters>>) {
+    parameters.write().unwrap().expansion = expansion;
+}
+
+#[tauri::command]
+fn set_solver(
+    solver: Solver,
+    parameters: State<&'static RwLock<Parameters>>,
+) -> Result<(), InvalidSolver> {
+    parameters.write().unwrap().solver = solver.validate()?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_magnetic_field(
+    magnetic_field: Option<MagneticField>,
+    parameters: State<&'static RwLock<Parameters>>,
+) {
+    parameters.write().unwrap().magnetic_field = magnetic_field;
+}
+
+#[tauri::command]
+fn set_electric_field(
+    electric_field: Option<ElectricField>,
+    parameters: State<&'static RwLock<Parameters>>,
+) {
+    parameters.write().unwrap().electric_field = electric_field;
+}
+
+#[tauri::command]
+fn set_integrator(integrator: Integrator, parameters: State<&'static RwLock<Parameters>>) {
+    parameters.write().unwrap().integrator = integrator;
+}
+
+/// Loads `preset`, returning its expected period if it has one.
+#[tauri::command]
+fn load_preset(preset: Preset) -> Option<f64> {
+    load(preset.scenario())
 }
 
-fn stable_orbits(center: Vector2<f64>) -> [Arc<Boid>; 6] {
-    let mass_one = {
-        let boid = Boid::new(center.x, center.y, MASS_ONE);
-        boid.set_velocity(Vector2::new(0.0, 0.0));
-        Arc::new(boid)
+/// Replaces the running simulation with `scenario`, returning its expected period.
+fn load(scenario: Scenario) -> Option<f64> {
+    let mut min = MIN.lock().unwrap();
+    let mut max = MAX.lock().unwrap();
+    // The kept indexes are over the old bodies, so the next tick builds new ones.
+    let mut indexes = INDEXES.write().unwrap();
+    *indexes = None;
+
+    // Start the tree off around the new bodies, or the first tick would drop any outside it.
+    let bounds = scenario.bodies.iter().map(Boid::position).fold(
+        (
+            Vector2::new(f64::MAX, f64::MAX),
+            Vector2::new(f64::MIN, f64::MIN),
+        ),
+        |(min, max), position| {
+            (
+                Vector2::new(min.x.min(position.x), min.y.min(position.y)),
+                Vector2::new(max.x.max(position.x), max.y.max(position.y)),
+            )
+        },
+    );
+    (*min, *max) = bounds;
+    *BODIES.write().unwrap() = Arc::new(scenario.bodies.into_iter().collect());
+    *PARAMETERS.write().unwrap() = scenario.parameters;
+    scenario.period
+}
+
+/// Writes every body's state one step on into `next`. Everything is found from `bodies` before
+/// anything moves, so each body sees the same snapshot.
+fn advance(
+    bodies: &Bodies,
+    indexes: &Indexes,
+    parameters: &Parameters,
+    random: &mut Random,
+    next: &mut Bodies,
+) {
+    let states = match parameters.point_vortices {
+        Some(vortices) => advect(bodies, &indexes.tree, parameters, vortices),
+        None => integrate(bodies, indexes, parameters, random, next),
     };
-    let mass_two_speed = (GRAVITY * MASS_ONE / 100.0).sqrt();
-    let mass_two = {
-        let boid = Boid::new(center.x + 100.0, center.y, MASS_TWO);
-        boid.set_velocity(Vector2::new(0.0, mass_two_speed));
-        Arc::new(boid)
+    for (index, (mut position, velocity)) in states.into_iter().enumerate() {
+        if let Some(cell) = parameters.periodic_cell {
+            position = molecular::wrap(position, cell);
+        }
+        next.set_position(index, position);
+        next.set_velocity(index, velocity);
+    }
+}
+
+/// Moves point vortices with the flow the others induce. They have no inertia, so this is first
+/// order.
+fn advect(
+    bodies: &Bodies,
+    tree: &Quadtree,
+    parameters: &Parameters,
+    vortices: PointVortices,
+) -> Vec<(Vector2<f64>, Vector2<f64>)> {
+    let fmm = match parameters.solver {
+        Solver::Fmm { order } => Some(Fmm::new(
+            tree,
+            |particle| particle.circulation,
+            order,
+            vortices.softening,
+        )),
+        _ => None,
     };
-    let mass_three = {
-        let boid = Boid::new(center.x - 100.0, center.y, MASS_TWO);
-        boid.set_velocity(Vector2::new(0.0, -mass_two_speed));
-        Arc::new(boid)
+    bodies
+        .iter()
+        .map(|body| {
+            let velocity = match (parameters.solver, &fmm) {
+                (_, Some(fmm)) => fmm.induced_velocity(body),
+                (Solver::Direct, None) => {
+                    direct::calculate_induced_velocity(bodies, body, vortices)
+                }
+                (_, None) => {
+                    tree.calculate_induced_velocity(&body, parameters.opening.theta(), vortices)
+                }
+            };
+            (body.position() + velocity * parameters.dt, velocity)
+        })
+        .collect()
+}
+
+/// Gravity on `body` from walking `tree`, or from the group walk when there was one, adapting the
+/// body's opening angle in `next` as it goes when there is a target accuracy.
+fn tree_gravity(
+    body: BodyRef,
+    tree: &Quadtree,
+    parameters: &Parameters,
+    groups: Option<&GroupForces>,
+    next: &mut Bodies,
+) -> Vector2<f64> {
+    if let Some(groups) = groups {
+        return groups.get(body);
+    }
+    let (law, order) = (parameters.force_law(), parameters.expansion);
+    let Some(accuracy) = parameters.accuracy else {
+        return tree.calculate_force(&body, parameters.opening, &law, order);
     };
-    let mass_four = {
-        let boid = Boid::new(center.x + 50.0, center.y + 86.6, SATELITE_MASS);
-        boid.set_velocity(Vector2::new(-7.94, 4.58));
-        Arc::new(boid)
+    let theta = if body.opening_angle() > 0.0 {
+        body.opening_angle()
+    } else {
+        parameters.opening.theta()
     };
+    let (force, error) =
+        tree.estimate_force(&body, parameters.opening.with_theta(theta), &law, order);
+    if force.magnitude() == 0.0 {
+        // Nothing pulls on the body, such as when it is alone, so there is no error to compare.
+        return force;
+    }
+    let relative_error = error / force.magnitude();
+    next.set_opening_angle(
+        body.index(),
+        quadtree::adapt_opening_angle(theta, relative_error, accuracy, order),
+    );
+    force
+}
 
-    let mass_five = {
-        let boid = Boid::new(center.x + 50.0, center.y - 86.6, SATELITE_MASS);
-        boid.set_velocity(Vector2::new(7.94, 4.58));
-        Arc::new(boid)
+/// Pairwise forces on every body: gravity, electrostatics and short-range repulsion. Each body's
+/// gravitational acceleration is kept in `next` for the relative opening criterion.
+fn calculate_forces(
+    bodies: &Bodies,
+    tree: &Quadtree,
+    neighbours: &dyn SpatialIndex,
+    parameters: &Parameters,
+    next: &mut Bodies,
+) -> Vec<Vector2<f64>> {
+    let law = parameters.force_law();
+    let mesh = match parameters.solver {
+        Solver::ParticleMesh { cells } if parameters.self_gravity => Some(ParticleMesh::new(
+            bodies,
+            tree.outer_bounds(),
+            cells,
+            &law,
+            None,
+        )),
+        Solver::TreePm { cells, split } if parameters.self_gravity => Some(ParticleMesh::new(
+            bodies,
+            tree.outer_bounds(),
+            cells,
+            &law,
+            Some(split),
+        )),
+        _ => None,
+    };
+    let fmm = match (parameters.solver, law.law) {
+        (Solver::Fmm { order }, Law::Logarithmic(logarithmic)) if parameters.self_gravity => {
+            Some((
+                Fmm::new(tree, |particle| particle.mass, order, parameters.softening),
+                logarithmic,
+            ))
+        }
+        _ => None,
     };
+    let kd_tree = (parameters.self_gravity && parameters.solver == Solver::KdTree)
+        .then(|| KdTree::build(bodies));
+    let groups = match parameters.group_size {
+        Some(group_size)
+            if parameters.self_gravity
+                && parameters.solver != Solver::Direct
+                && mesh.is_none()
+                && fmm.is_none()
+                && kd_tree.is_none() =>
+        {
+            Some(tree.calculate_group_forces(
+                parameters.opening,
+                &law,
+                parameters.expansion,
+                group_size,
+            ))
+        }
+        _ => None,
+    };
+    bodies
+        .iter()
+        .map(|body| {
+            let gravity = match (parameters.self_gravity, parameters.solver, &mesh, &fmm) {
+                (false, ..) => Vector2::default(),
+                (true, _, Some(mesh), _) => {
+                    mesh.calculate_force(body, tree, parameters.opening, law, parameters.expansion)
+                }
+                (true, _, _, Some((fmm, logarithmic))) => {
+                    fmm.gravity(body, logarithmic.gravity, logarithmic.length)
+                }
+                (true, Solver::Direct, None, None) => direct::calculate_force(bodies, body, &law),
+                (true, ..) => match &kd_tree {
+                    Some(kd_tree) => {
+                        spatial::calculate_force(kd_tree, body, parameters.opening.theta(), &law)
+                    }
+                    None => tree_gravity(body, tree, parameters, groups.as_ref(), next),
+                },
+            };
+            next.set_acceleration(body.index(), gravity * (1.0 / body.mass()));
+            let electric = match (parameters.electrostatics, parameters.solver) {
+                (None, _) => Vector2::default(),
+                (Some(coulomb), Solver::Direct) => {
+                    direct::calculate_electric_force(bodies, body, &coulomb)
+                }
+                (Some(coulomb), _) => {
+                    tree.calculate_electric_force(&body, parameters.opening.theta(), &coulomb)
+                }
+            };
+            let short_range =
+                parameters
+                    .lennard_jones
+                    .map_or(Vector2::default(), |lennard_jones| {
+                        lennard_jones.calculate_force(neighbours, body, parameters.periodic_cell)
+                    });
+            gravity + electric + short_range
+        })
+        .collect()
+}
 
-    let moon_sun_speed = orbital_speed(110.0, MASS_ONE);
-    let moon_speed = orbital_speed(10.0, MASS_TWO);
+/// The index that short-range interactions search, when any of them is on and it is not the
+/// gravity tree.
+fn build_neighbour_index(
+    bodies: &Bodies,
+    parameters: &Parameters,
+) -> Option<Box<dyn SpatialIndex + Send + Sync>> {
+    let cutoff = parameters
+        .lennard_jones
+        .map_or(0.0, |lennard_jones| lennard_jones.cutoff);
+    let perception = parameters
+        .flocking
+        .map_or(0.0, |flocking| flocking.perception);
+    let kernel = match parameters.sph {
+        Some(_) => bodies
+            .iter()
+            .filter_map(BodyRef::gas)
+            .fold(0.0, |largest, gas| {
+                (2.0 * gas.smoothing_length).max(largest)
+            }),
+        None => 0.0,
+    };
+    let reach = cutoff.max(perception).max(kernel);
+    let short_range = parameters.lennard_jones.is_some()
+        || parameters.flocking.is_some()
+        || parameters.sph.is_some();
+    match parameters.neighbour_index {
+        _ if !short_range => None,
+        NeighbourIndex::Quadtree => None,
+        NeighbourIndex::Grid if reach > 0.0 => Some(Box::new(UniformGrid::new(bodies, reach))),
+        NeighbourIndex::Grid => Some(Box::new(UniformGrid::build(bodies))),
+        NeighbourIndex::KdTree => Some(Box::new(KdTree::build(bodies))),
+    }
+}
 
-    let mass_six = {
-        let boid = Boid::new(center.x + 110.0, center.y, SATELITE_MASS);
-        boid.set_velocity(Vector2::new(0.0, moon_sun_speed + moon_speed));
-        Arc::new(boid)
+/// Integrates every body under the forces acting on it, writing what else changes about it, such
+/// as its gas, into `next`.
+fn integrate(
+    bodies: &Bodies,
+    indexes: &Indexes,
+    parameters: &Parameters,
+    random: &mut Random,
+    next: &mut Bodies,
+) -> Vec<(Vector2<f64>, Vector2<f64>)> {
+    let dt = parameters.dt;
+    let (tree, neighbours) = (&indexes.tree, indexes.neighbours());
+    let (gas, hydrodynamics): (Vec<_>, Vec<_>) = match parameters.sph {
+        Some(sph) => {
+            let gas = sph.update_densities(bodies, neighbours);
+            let largest_smoothing_length = gas
+                .iter()
+                .flatten()
+                .fold(0.0, |largest, gas| gas.smoothing_length.max(largest));
+            let hydrodynamics = (0..bodies.len())
+                .map(|index| {
+                    sph.calculate_acceleration(
+                        bodies,
+                        index,
+                        &gas,
+                        neighbours,
+                        2.0 * largest_smoothing_length,
+                    )
+                })
+                .collect();
+            (gas, hydrodynamics)
+        }
+        None => (Vec::new(), vec![(Vector2::default(), 0.0); bodies.len()]),
     };
 
-    [
-        mass_one, mass_two, mass_three, mass_four, mass_five, mass_six,
-    ]
-}
+    let forces = calculate_forces(bodies, tree, neighbours, parameters, next);
 
-fn main() {
-    {
-        let mut lock = BOIDS.write().unwrap();
-<<<<<<< HEAD
-        for boid in stable_orbits(Vector2 {
-            x: CENTER_X,
-            y: CENTER_Y,
-        }) {
-            lock.push(boid);
-=======
-        for boid in crate::star_system::StarSystem::generate_disk_system(100, 100.0) {
-            lock.push(Arc::new(boid));
->>>>>>> 0289d2b (add signals and star system)
+    let mut states: Vec<_> = bodies
+        .iter()
+        .zip(forces)
+        .zip(&hydrodynamics)
+        .map(|((body, force), (pressure, _))| {
+            let (position, charge, mass) = (body.position(), body.charge(), body.mass());
+            let external = parameters
+                .electric_field
+                .map_or(Vector2::default(), |field| field.at(position) * charge);
+            let steering = parameters.flocking.map_or(Vector2::default(), |flocking| {
+                let flockmates: Vec<_> = neighbours
+                    .query_radius(position, flocking.perception)
+                    .iter()
+                    .map(|particle| bodies.get(particle.index))
+                    .collect();
+                flocking.steering(body, &flockmates)
+            });
+            let acceleration = (force + external) * (1.0 / mass) + steering + *pressure;
+            let gyrofrequency = parameters
+                .magnetic_field
+                .map_or(0.0, |field| field.at(position) * charge / mass);
+            let (new_position, new_velocity) = parameters.integrator.step(
+                position,
+                body.velocity(),
+                acceleration,
+                gyrofrequency,
+                dt,
+            );
+            match parameters.flocking {
+                Some(flocking) => {
+                    // Every integrator drifts with the new velocity, so redo that with the capped
+                    // one.
+                    let new_velocity = flocking.limit_speed(new_velocity);
+                    (position + new_velocity * dt, new_velocity)
+                }
+                None => (new_position, new_velocity),
+            }
+        })
+        .collect();
+
+    for (index, (gas, (_, heating))) in gas.into_iter().zip(hydrodynamics).enumerate() {
+        if let Some(gas) = gas {
+            next.set_gas(
+                index,
+                Some(Gas {
+                    internal_energy: (gas.internal_energy + heating * dt).max(0.0),
+                    ..gas
+                }),
+            );
         }
     }
 
-    tauri::Builder::default()
-        .setup(|_| {
-            std::thread::spawn(|| loop {
-                let mut min = MIN.lock().unwrap();
-                let mut max = MAX.lock().unwrap();
+    if let Some(thermostat) = parameters.thermostat {
+        let mut velocities: Vec<_> = states.iter().map(|(_, velocity)| *velocity).collect();
+        thermostat.apply(&mut velocities, bodies.masses(), dt, random);
+        for ((_, velocity), thermalised) in states.iter_mut().zip(velocities) {
+            *velocity = thermalised;
+        }
+    }
+    states
+}
 
-                let mut tree = Quadtree::new(boundary::Boundary::new(*min, *max));
+/// Bounds for a new tree around bodies between `min` and `max`, with some room to spare so that it
+/// keeps fitting them for a while as they spread out.
+fn tree_bounds(min: Vector2<f64>, max: Vector2<f64>) -> Boundary {
+    let fitted = Boundary::new(min, max);
+    Boundary::from_center(fitted.center(), fitted.half_size() * TREE_MARGIN)
+}
 
-                for body in BOIDS.read().unwrap().iter() {
-                    if let Ok(()) = tree.insert(body.clone()) {}
-                }
+/// A new tree over `bodies` within `bounds`, built bottom up from their Morton order.
+fn build_tree(bodies: &Bodies, bounds: Boundary) -> Quadtree {
+    let mut particles = bodies.particles();
+    morton::sort(&mut particles, bounds);
+    Quadtree::from_sorted(bounds, &particles)
+}
+
+/// The tree over `next`, brought up to date from a copy of `tree` when the bodies still fit it, or
+/// else rebuilt over `bounds`.
+fn next_tree(tree: &Quadtree, next: &mut Bodies, bounds: Boundary) -> Quadtree {
+    let mut tree = Quadtree::clone(tree);
+    if !tree.update(|particle| next.get(particle.index).particle()) {
+        // Put the bodies in Morton order first, so that the force loop visits them in roughly
+        // the order they sit in the tree.
+        next.permute(&morton::order(next.positions(), bounds));
+        tree = build_tree(next, bounds);
+    }
+    tree
+}
+
+/// Runs the simulation forever, one tick every [`TIMESTEP`] milliseconds.
+fn simulate() -> ! {
+    let mut random = Random::from_time();
+    // The snapshot before last, whose buffers the next step is written into once no reader still
+    // holds it.
+    let mut spare: Option<Arc<Bodies>> = None;
+    loop {
+        let mut min = MIN.lock().unwrap();
+        let mut max = MAX.lock().unwrap();
+
+        let parameters = *PARAMETERS.read().unwrap();
+        let (kept, current) = snapshot(&INDEXES, &BODIES);
+        let indexes = kept.unwrap_or_else(|| {
+            let tree = build_tree(&current, tree_bounds(*min, *max));
+            Arc::new(Indexes::new(tree, &current, &parameters))
+        });
+        let mut next = bodies::next_step(spare.take(), &current);
 
-                let mut new_max = Vector2::new(None, None);
-                let mut new_min = Vector2::new(None, None);
-
-                for body in BOIDS.write().unwrap().iter() {
-                    let force = tree.calculate_force(body, THETA);
-                    let acceleration = force * (1.0 / body.mass());
-                    let new_velocity = body.velocity() + acceleration * DT;
-                    let new_position = body.position() + new_velocity * DT;
-                    body.set_velocity(new_velocity);
-                    body.set_position(new_position);
-
-                    // Resize the tree
-                    match new_min.x {
-                        Some(x) => {
-                            if new_position.x < x {
-                                new_min.x = Some(new_position.x);
-                            }
-                        }
-                        None => {
-                            new_min.x = Some(new_position.x);
-                        }
+        let mut new_max = Vector2::new(None, None);
+        let mut new_min = Vector2::new(None, None);
+
+        advance(&current, &indexes, &parameters, &mut random, &mut next);
+        for &new_position in next.positions() {
+            // Resize the tree
+            match new_min.x {
+                Some(x) => {
+                    if new_position.x < x {
+                        new_min.x = Some(new_position.x);
                     }
-                    match new_min.y {
-                        Some(y) => {
-                            if new_position.y < y {
-                                new_min.y = Some(new_position.y);
-                            }
-                        }
-                        None => {
-                            new_min.y = Some(new_position.y);
-                        }
+                }
+                None => {
+                    new_min.x = Some(new_position.x);
+                }
+            }
+            match new_min.y {
+                Some(y) => {
+                    if new_position.y < y {
+                        new_min.y = Some(new_position.y);
                     }
-                    match new_max.x {
-                        Some(x) => {
-                            if new_position.x > x {
-                                new_max.x = Some(new_position.x);
-                            }
-                        }
-                        None => {
-                            new_max.x = Some(new_position.x);
-                        }
+                }
+                None => {
+                    new_min.y = Some(new_position.y);
+                }
+            }
+            match new_max.x {
+                Some(x) => {
+                    if new_position.x > x {
+                        new_max.x = Some(new_position.x);
                     }
-                    match new_max.y {
-                        Some(y) => {
-                            if new_position.y > y {
-                                new_max.y = Some(new_position.y);
-                            }
-                        }
-                        None => {
-                            new_max.y = Some(new_position.y);
-                        }
+                }
+                None => {
+                    new_max.x = Some(new_position.x);
+                }
+            }
+            match new_max.y {
+                Some(y) => {
+                    if new_position.y > y {
+                        new_max.y = Some(new_position.y);
                     }
                 }
-                min.x = new_min.x.unwrap_or(0.0);
-                min.y = new_min.y.unwrap_or(0.0);
-                max.x = new_max.x.unwrap_or(0.0);
-                max.y = new_max.y.unwrap_or(0.0);
-                {
-                    use std::mem::replace;
-                    let mut tree_state = TREE_STATE.write().expect("Could not acquire bounds lock");
-                    let new_state = TreeState {
-                        boundaries: tree.boundaries(),
-                        center_of_mass: tree.center_of_mass(),
-                        outer_bounds: tree.outer_bounds(),
-                        center: tree.outer_bounds().center(),
-                    };
-                    let _ = replace(&mut *tree_state, Some(new_state));
+                None => {
+                    new_max.y = Some(new_position.y);
                 }
-                std::thread::sleep(Duration::from_millis(TIMESTEP.into()));
-            });
+            }
+        }
+        min.x = new_min.x.unwrap_or(0.0);
+        min.y = new_min.y.unwrap_or(0.0);
+        max.x = new_max.x.unwrap_or(0.0);
+        max.y = new_max.y.unwrap_or(0.0);
+        // Most bodies stay in the same leaf from one tick to the next, so the tree is only rebuilt
+        // once it no longer fits them. Bringing it up to date here means queries between ticks
+        // see the bodies where they are. Readers may still be using the published tree, so the
+        // update goes into a copy of it.
+        let tree = next_tree(&indexes.tree, &mut next, tree_bounds(*min, *max));
+        {
+            use std::mem::replace;
+            let mut tree_state = TREE_STATE.write().expect("Could not acquire bounds lock");
+            let new_state = TreeState {
+                boundaries: tree.boundaries(),
+                center_of_mass: tree.center_of_mass(),
+                outer_bounds: tree.outer_bounds(),
+                center: tree.outer_bounds().center(),
+            };
+            let _ = replace(&mut *tree_state, Some(new_state));
+        }
+        // The neighbour index for the next tick is built here, over the bodies it will start
+        // from, so that the frontend can query it in between.
+        let indexes = Indexes::new(tree, &next, &parameters);
+        {
+            let mut kept = INDEXES.write().unwrap();
+            *BODIES.write().unwrap() = Arc::new(next);
+            *kept = Some(Arc::new(indexes));
+        }
+        spare = Some(current);
+        std::thread::sleep(Duration::from_millis(TIMESTEP.into()));
+    }
+}
+
+fn main() {
+    load(Preset::StableOrbits.scenario());
+
+    tauri::Builder::default()
+        .setup(|_| {
+            std::thread::spawn(simulate);
             Ok(())
         })
-        .manage(&BOIDS)
+        .manage(&*BODIES)
         .manage(&TREE_STATE)
-        .invoke_handler(tauri::generate_handler![get_bodies, get_tree])
+        .manage(&INDEXES)
+        .manage(&PARAMETERS)
+        .invoke_handler(tauri::generate_handler![
+            get_bodies,
+            get_visible_bodies,
+            get_bodies_near,
+            pick_body,
+            get_tree,
+            get_orbits,
+            get_units,
+            get_temperature,
+            set_force_law,
+            set_self_gravity,
+            set_flocking,
+            set_point_vortices,
+            set_lennard_jones,
+            set_periodic_cell,
+            set_thermostat,
+            set_softening,
+            set_sph,
+            set_electrostatics,
+            set_solver,
+            set_expansion,
+            set_opening,
+            set_accuracy,
+            set_group_size,
+            set_neighbour_index,
+            set_magnetic_field,
+            set_electric_field,
+            set_integrator,
+            load_preset
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steps `scenario` on for `ticks` ticks as [`simulate`] does, with a fresh tree each tick.
+    pub(crate) fn run(scenario: Scenario, ticks: usize) -> Bodies {
+        let mut bodies: Bodies = scenario.bodies.into_iter().collect();
+        let mut random = Random::new(1);
+        for _ in 0..ticks {
+            let (min, max) = bodies.positions().iter().fold(
+                (
+                    Vector2::new(f64::MAX, f64::MAX),
+                    Vector2::new(f64::MIN, f64::MIN),
+                ),
+                |(min, max), position| {
+                    (
+                        Vector2::new(min.x.min(position.x), min.y.min(position.y)),
+                        Vector2::new(max.x.max(position.x), max.y.max(position.y)),
+                    )
+                },
+            );
+            let tree = build_tree(&bodies, tree_bounds(min, max));
+            let indexes = Indexes::new(tree, &bodies, &scenario.parameters);
+            let mut next = bodies.clone();
+            advance(
+                &bodies,
+                &indexes,
+                &scenario.parameters,
+                &mut random,
+                &mut next,
+            );
+            bodies = next;
+        }
+        bodies
+    }
+
+    /// Distance of the farthest body from the centre of mass.
+    fn extent(bodies: &Bodies) -> f64 {
+        let mass: f64 = bodies.masses().iter().sum();
+        let center = bodies.iter().fold(Vector2::default(), |sum, body| {
+            sum + body.position() * body.mass()
+        }) / mass;
+        bodies
+            .positions()
+            .iter()
+            .map(|&position| (position - center).magnitude())
+            .fold(0.0, f64::max)
+    }
+
+    /// Tree forces on every body after moving them with `shift`, from the tree brought up to date
+    /// for the next tick and from a fresh build over the same bounds. Also says whether the tree
+    /// was rebuilt.
+    fn forces_after(
+        bodies: &Bodies,
+        shift: impl Fn(BodyRef) -> Vector2<f64>,
+    ) -> (Vec<Vector2<f64>>, Vec<Vector2<f64>>, bool) {
+        let bounds = |bodies: &Bodies| {
+            let fitted = spatial::bounding_box(&bodies.particles());
+            tree_bounds(fitted.min, fitted.max)
+        };
+        let tree = build_tree(bodies, bounds(bodies));
+        let mut next = bodies.clone();
+        for body in bodies.iter() {
+            next.set_position(body.index(), body.position() + shift(body));
+        }
+        let next_bounds = bounds(&next);
+        let updated = next_tree(&tree, &mut next, next_bounds);
+        let fresh = build_tree(&next, updated.outer_bounds());
+        let forces = |tree: &Quadtree| {
+            next.iter()
+                .map(|body| {
+                    tree.calculate_force(
+                        &body,
+                        OpeningCriterion::Geometric { theta: 0.5 },
+                        &force_law::Newtonian { gravity: 1.0 },
+                        ExpansionOrder::Quadrupole,
+                    )
+                })
+                .collect()
+        };
+        let rebuilt = updated.outer_bounds() != tree.outer_bounds();
+        (forces(&updated), forces(&fresh), rebuilt)
+    }
+
+    #[test]
+    fn updated_tree_matches_a_fresh_build() {
+        let mut random = Random::new(21);
+        let bodies: Bodies = (0..500)
+            .map(|_| {
+                Boid::new(
+                    random.uniform() * 1000.0,
+                    random.uniform() * 1000.0,
+                    1.0 + random.uniform() * 9.0,
+                )
+            })
+            .collect();
+        let spread: Vec<_> = (0..bodies.len())
+            .map(|_| {
+                Vector2::new(random.uniform(), random.uniform()) * 2.0 - Vector2::new(1.0, 1.0)
+            })
+            .collect();
+        for (case, shift, rebuilds) in [
+            // Too little to leave any leaf.
+            (
+                "within a leaf",
+                &(|body: BodyRef| spread[body.index()] * 1e-9) as &dyn Fn(BodyRef) -> Vector2<f64>,
+                false,
+            ),
+            // Far enough to cross into other leaves, staying inside the tree.
+            (
+                "between leaves",
+                &|body: BodyRef| {
+                    let inwards = Vector2::new(500.0, 500.0) - body.position();
+                    spread[body.index()] * 5.0 + inwards * 0.01
+                },
+                false,
+            ),
+            (
+                "out of the tree",
+                &|body: BodyRef| {
+                    if body.index() == 0 {
+                        Vector2::new(2000.0, 2000.0)
+                    } else {
+                        Vector2::default()
+                    }
+                },
+                true,
+            ),
+        ] {
+            let (updated, fresh, rebuilt) = forces_after(&bodies, shift);
+            assert_eq!(rebuilt, rebuilds, "{case}");
+            for (updated, fresh) in updated.iter().zip(&fresh) {
+                assert!(
+                    (*updated - *fresh).magnitude() < 1e-9 * fresh.magnitude(),
+                    "{case}: {updated:?} against {fresh:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn opening_angle_is_kept_without_force() {
+        let mut scenario = Preset::StableOrbits.scenario();
+        scenario.bodies.truncate(1);
+        scenario.parameters.accuracy = Some(1e-3);
+        let bodies = run(scenario, 2);
+        // With nothing to measure the error against, it keeps the angle it started with.
+        assert!(bodies.get(0).opening_angle() == 0.0);
+    }
+
+    #[test]
+    fn presets_stay_bounded() {
+        for preset in [Preset::StableOrbits, Preset::SolarSystem] {
+            let start = extent(&preset.scenario().bodies.into_iter().collect());
+            let end = extent(&run(preset.scenario(), 2000));
+            assert!(end < 2.0 * start, "{preset:?}: {start} to {end}");
+        }
+    }
+}
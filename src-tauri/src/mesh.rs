@@ -0,0 +1,300 @@
+//! Particle-mesh gravity: masses are spread onto a grid, convolved with the force kernel by FFT,
+//! and the field interpolated back to each body.
+//!
+//! The grid is zero-padded to twice its size, Hockney's method, so the convolution sees isolated
+//! rather than periodic boundaries.
+
+use std::f64::consts::PI;
+
+use crate::{
+    bodies::{Bodies, BodyRef},
+    boundary::Boundary,
+    fft::{fft_2d, Complex},
+    force_law::{ExpansionOrder, ForceLaw},
+    quadtree::{OpeningCriterion, Quadtree},
+    vector::Vector2,
+};
+
+/// Complementary error function, from Abramowitz and Stegun 7.1.26, accurate to about `1.5e-7`.
+pub fn erfc(x: f64) -> f64 {
+    if x < 0.0 {
+        return 2.0 - erfc(-x);
+    }
+    let t = 1.0 / (1.0 + 0.327_591_1 * x);
+    let polynomial = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    polynomial * (-x * x).exp()
+}
+
+/// Fraction of an inverse-square force at `distance` that is left to the short-range part when it
+/// is split on the scale `split`, as in Gadget's tree-PM method.
+pub fn short_range_fraction(distance: f64, split: f64) -> f64 {
+    let scaled = distance / (2.0 * split);
+    erfc(scaled) + distance / (split * PI.sqrt()) * (-scaled * scaled).exp()
+}
+
+/// How many split scales out the short-range force is followed, where it has fallen below half a
+/// percent of the full force.
+const SHORT_RANGE_REACH: f64 = 5.0;
+
+/// A force law with its long-range part removed, for the tree half of tree-PM.
+#[derive(Debug, Clone, Copy)]
+pub struct ShortRange<L> {
+    pub law: L,
+    pub split: f64,
+}
+
+impl<L: ForceLaw> ForceLaw for ShortRange<L> {
+    fn magnitude(&self, distance: f64, mass: f64, source_mass: f64) -> f64 {
+        self.law.magnitude(distance, mass, source_mass) * short_range_fraction(distance, self.split)
+    }
+}
+
+/// Gravitational acceleration sampled on a square grid.
+pub struct ParticleMesh {
+    origin: Vector2<f64>,
+    spacing: f64,
+    cells: usize,
+    /// Scale of the tree-PM split, if the grid only holds the long-range force.
+    split: Option<f64>,
+    acceleration: Vec<Vector2<f64>>,
+}
+
+impl ParticleMesh {
+    /// Solves for the acceleration from `bodies` on a `cells` by `cells` grid over `bounds`, which
+    /// must be a power of two.
+    ///
+    /// With a `split`, in grid cells, only the long-range part of `law` beyond that scale is kept,
+    /// leaving the rest to the tree. Without one, the law is softened over a cell instead, as the
+    /// grid cannot resolve anything smaller.
+    pub fn new<L: ForceLaw>(
+        bodies: &Bodies,
+        bounds: Boundary,
+        cells: usize,
+        law: &L,
+        split: Option<f64>,
+    ) -> Self {
+        #[allow(clippy::cast_precision_loss)]
+        let spacing = (bounds.max.x - bounds.min.x) / (cells - 1) as f64;
+        let split = split.map(|split| split * spacing);
+        let mut mesh = Self {
+            origin: bounds.min,
+            spacing,
+            cells,
+            split,
+            acceleration: Vec::new(),
+        };
+
+        let padded = 2 * cells;
+        let mut density = vec![Complex::default(); padded * padded];
+        for (&position, &mass) in bodies.positions().iter().zip(bodies.masses()) {
+            for (index, weight) in mesh.cloud_in_cell(position) {
+                let (x, y) = (index % cells, index / cells);
+                density[y * padded + x].re += weight * mass;
+            }
+        }
+        fft_2d(&mut density, padded, false);
+
+        // Acceleration at a displacement from a unit mass, with the far half of the padded grid
+        // standing in for negative displacements.
+        let mut kernel_x = vec![Complex::default(); padded * padded];
+        let mut kernel_y = vec![Complex::default(); padded * padded];
+        for y in 0..padded {
+            for x in 0..padded {
+                // Indices past the middle wrap round to negative displacements.
+                #[allow(clippy::cast_precision_loss)]
+                let offset = |i: usize| {
+                    let steps = if i < cells {
+                        i as f64
+                    } else {
+                        i as f64 - padded as f64
+                    };
+                    steps * spacing
+                };
+                let displacement = Vector2::new(offset(x), offset(y));
+                let distance = displacement.magnitude();
+                if distance == 0.0 || x == cells || y == cells {
+                    continue;
+                }
+                let magnitude = if let Some(split) = split {
+                    law.magnitude(distance, 1.0, 1.0)
+                        * (1.0 - short_range_fraction(distance, split))
+                } else {
+                    let softened = distance.hypot(spacing);
+                    law.magnitude(softened, 1.0, 1.0) * distance / softened
+                };
+                // The pull is back towards the source, against the displacement.
+                let acceleration = displacement * (-magnitude / distance);
+                kernel_x[y * padded + x].re = acceleration.x;
+                kernel_y[y * padded + x].re = acceleration.y;
+            }
+        }
+        fft_2d(&mut kernel_x, padded, false);
+        fft_2d(&mut kernel_y, padded, false);
+
+        for (kernel, mass) in kernel_x.iter_mut().zip(&density) {
+            *kernel = *kernel * *mass;
+        }
+        for (kernel, mass) in kernel_y.iter_mut().zip(&density) {
+            *kernel = *kernel * *mass;
+        }
+        fft_2d(&mut kernel_x, padded, true);
+        fft_2d(&mut kernel_y, padded, true);
+
+        mesh.acceleration = (0..cells * cells)
+            .map(|index| {
+                let (x, y) = (index % cells, index / cells);
+                let cell = y * padded + x;
+                Vector2::new(kernel_x[cell].re, kernel_y[cell].re)
+            })
+            .collect();
+        mesh
+    }
+
+    /// The four grid points around `position` and their cloud-in-cell weights. Positions off the
+    /// grid are clamped to its edge.
+    fn cloud_in_cell(&self, position: Vector2<f64>) -> [(usize, f64); 4] {
+        let last = self.cells - 2;
+        let split = |coordinate: f64| {
+            let scaled = coordinate.max(0.0) / self.spacing;
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let cell = (scaled.floor() as usize).min(last);
+            #[allow(clippy::cast_precision_loss)]
+            let fraction = (scaled - cell as f64).min(1.0);
+            (cell, fraction)
+        };
+        let offset = position - self.origin;
+        let (x, fx) = split(offset.x);
+        let (y, fy) = split(offset.y);
+        let index = |x: usize, y: usize| y * self.cells + x;
+        [
+            (index(x, y), (1.0 - fx) * (1.0 - fy)),
+            (index(x + 1, y), fx * (1.0 - fy)),
+            (index(x, y + 1), (1.0 - fx) * fy),
+            (index(x + 1, y + 1), fx * fy),
+        ]
+    }
+
+    /// Acceleration at `position`, interpolated with the same weights used to deposit the masses
+    /// so that bodies do not accelerate themselves.
+    pub fn acceleration(&self, position: Vector2<f64>) -> Vector2<f64> {
+        self.cloud_in_cell(position)
+            .iter()
+            .fold(Vector2::default(), |total, &(index, weight)| {
+                total + self.acceleration[index] * weight
+            })
+    }
+
+    /// Gravitational force on `body`, adding the short-range part from `tree` for tree-PM.
+    pub fn calculate_force<L: ForceLaw + Copy>(
+        &self,
+        body: BodyRef,
+        tree: &Quadtree,
+        criterion: OpeningCriterion,
+        law: L,
+        order: ExpansionOrder,
+    ) -> Vector2<f64> {
+        let long_range = self.acceleration(body.position()) * body.mass();
+        match self.split {
+            Some(split) => {
+                let short_range = ShortRange { law, split };
+                long_range
+                    + tree.calculate_short_range_force(
+                        &body,
+                        criterion,
+                        &short_range,
+                        order,
+                        SHORT_RANGE_REACH * split,
+                    )
+            }
+            None => long_range,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{boid::Boid, direct, force_law::Newtonian, random::Random, spatial::SpatialIndex};
+
+    /// `count` bodies of mass between 1 and 10 scattered at random over `[min, max]` squared.
+    fn scattered(random: &mut Random, count: usize, min: f64, max: f64) -> Vec<Boid> {
+        (0..count)
+            .map(|_| {
+                Boid::new(
+                    min + random.uniform() * (max - min),
+                    min + random.uniform() * (max - min),
+                    1.0 + random.uniform() * 9.0,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn far_field_matches_direct_summation() {
+        let mut random = Random::new(11);
+        // A cluster in one corner, pulling on light probes in the other.
+        let mut boids = scattered(&mut random, 100, 0.0, 10.0);
+        let probes = boids.len();
+        boids.extend(
+            scattered(&mut random, 20, 60.0, 100.0)
+                .into_iter()
+                .map(|probe| {
+                    let position = probe.position();
+                    Boid::new(position.x, position.y, 1e-6)
+                }),
+        );
+        let bodies: Bodies = boids.into_iter().collect();
+        let tree = Quadtree::build(&bodies);
+        let law = Newtonian { gravity: 1.0 };
+        let mesh = ParticleMesh::new(&bodies, tree.outer_bounds(), 64, &law, None);
+        for body in bodies.iter().skip(probes) {
+            let exact = direct::calculate_force(&bodies, body, &law);
+            let approximate = mesh.calculate_force(
+                body,
+                &tree,
+                OpeningCriterion::Geometric { theta: 0.5 },
+                law,
+                ExpansionOrder::Monopole,
+            );
+            assert!(
+                (approximate - exact).magnitude() < 1e-2 * exact.magnitude(),
+                "{approximate:?} against {exact:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn tree_pm_matches_direct_summation() {
+        let mut random = Random::new(12);
+        let bodies: Bodies = scattered(&mut random, 300, 0.0, 100.0)
+            .into_iter()
+            .collect();
+        let tree = Quadtree::build(&bodies);
+        let law = Newtonian { gravity: 1.0 };
+        let mesh = ParticleMesh::new(&bodies, tree.outer_bounds(), 64, &law, Some(1.25));
+        let criterion = OpeningCriterion::Geometric { theta: 0.5 };
+        let relative_errors = |force: &dyn Fn(BodyRef) -> Vector2<f64>| {
+            let mut errors: Vec<_> = bodies
+                .iter()
+                .map(|body| {
+                    let exact = direct::calculate_force(&bodies, body, &law);
+                    (force(body) - exact).magnitude() / exact.magnitude()
+                })
+                .collect();
+            errors.sort_by(f64::total_cmp);
+            errors
+        };
+        let errors = relative_errors(&|body| {
+            mesh.calculate_force(body, &tree, criterion, law, ExpansionOrder::Quadrupole)
+        });
+        assert!(errors[errors.len() / 2] < 0.03, "{errors:?}");
+        assert!(errors[errors.len() - 1] < 0.3, "{errors:?}");
+
+        // The grid alone misses the short-range part that the tree makes up.
+        let errors = relative_errors(&|body| mesh.acceleration(body.position()) * body.mass());
+        assert!(errors[errors.len() / 2] > 0.3, "{errors:?}");
+    }
+}
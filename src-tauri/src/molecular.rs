@@ -0,0 +1,285 @@
+//! Molecular dynamics: short-range Lennard-Jones interactions, periodic boundaries and thermostats.
+//!
+//! Everything runs in reduced units, where the particle mass, `sigma`, `epsilon` and Boltzmann's
+//! constant are all one, so temperatures are given as `k_B T`.
+
+use crate::{
+    bodies::BodyRef,
+    boid::Boid,
+    boundary::Boundary,
+    force_law::ForceLaw,
+    random::Random,
+    scenario::{Parameters, Scenario},
+    spatial::SpatialIndex,
+    traits::Intersect,
+    units::UnitSystem,
+    vector::Vector2,
+};
+
+/// Reduced time advanced each tick.
+const DT: f64 = 0.005;
+
+/// The 12-6 Lennard-Jones interaction, cut off at `cutoff`. The `mass` arguments of [`ForceLaw`]
+/// are ignored, as every particle is the same species.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct LennardJones {
+    /// Depth of the potential well.
+    pub epsilon: f64,
+    /// Separation at which the potential crosses zero.
+    pub sigma: f64,
+    pub cutoff: f64,
+}
+
+impl ForceLaw for LennardJones {
+    fn magnitude(&self, distance: f64, _mass: f64, _source_mass: f64) -> f64 {
+        if distance > self.cutoff {
+            return 0.0;
+        }
+        let attractive = (self.sigma / distance).powi(6);
+        -24.0 * self.epsilon * (2.0 * attractive * attractive - attractive) / distance
+    }
+}
+
+impl LennardJones {
+    /// Force on `body` from every particle within the cutoff, found with `neighbours`. In a periodic
+    /// `cell` the nearest image of each particle is used, which needs the cutoff to be less than
+    /// half the cell.
+    pub fn calculate_force(
+        &self,
+        neighbours: &dyn SpatialIndex,
+        body: BodyRef,
+        cell: Option<Boundary>,
+    ) -> Vector2<f64> {
+        let position = body.position();
+        let images = match cell {
+            Some(cell) => {
+                let size = cell.max - cell.min;
+                [-1.0, 0.0, 1.0]
+                    .iter()
+                    .flat_map(|&x| [-1.0, 0.0, 1.0].map(|y| Vector2::new(x * size.x, y * size.y)))
+                    // Only look across the walls the cutoff actually reaches over.
+                    .filter(|&offset| {
+                        cell.intersects(&Boundary::from_center(position + offset, self.cutoff))
+                    })
+                    .collect()
+            }
+            None => vec![Vector2::default()],
+        };
+
+        let mut force = Vector2::default();
+        for offset in images {
+            // Searching around the shifted body finds the images of particles past the wall.
+            let image = position + offset;
+            for other in neighbours.query_radius(image, self.cutoff) {
+                if other.index != body.index() {
+                    force = force + self.force(other.position - image, 1.0, 1.0);
+                }
+            }
+        }
+        force
+    }
+}
+
+/// Keeps the temperature near a target by adjusting velocities after each step.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum Thermostat {
+    /// Rescales every velocity so the temperature relaxes towards `temperature` over
+    /// `time_constant`. Simple and smooth, but does not sample the canonical ensemble.
+    Berendsen {
+        temperature: f64,
+        time_constant: f64,
+    },
+    /// Adds friction and matching random kicks to each particle, which samples the canonical
+    /// ensemble at `temperature`.
+    Langevin { temperature: f64, friction: f64 },
+}
+
+impl Thermostat {
+    /// Adjusts the `velocities` of particles with `masses` after a step of `dt`.
+    pub fn apply(
+        self,
+        velocities: &mut [Vector2<f64>],
+        masses: &[f64],
+        dt: f64,
+        random: &mut Random,
+    ) {
+        match self {
+            Self::Berendsen {
+                temperature,
+                time_constant,
+            } => {
+                let current = kinetic_temperature(velocities, masses);
+                if current == 0.0 {
+                    return;
+                }
+                let scale = (1.0 + dt / time_constant * (temperature / current - 1.0))
+                    .max(0.0)
+                    .sqrt();
+                for velocity in velocities {
+                    *velocity = *velocity * scale;
+                }
+            }
+            Self::Langevin {
+                temperature,
+                friction,
+            } => {
+                // The exact solution of the Ornstein-Uhlenbeck process over the step.
+                let decay = (-friction * dt).exp();
+                for (velocity, mass) in velocities.iter_mut().zip(masses) {
+                    let spread = ((1.0 - decay * decay) * temperature / mass).sqrt();
+                    let kick = Vector2::new(random.gaussian(), random.gaussian()) * spread;
+                    *velocity = *velocity * decay + kick;
+                }
+            }
+        }
+    }
+}
+
+/// `k_B T` from the kinetic energy, with two degrees of freedom per particle.
+pub fn kinetic_temperature(velocities: &[Vector2<f64>], masses: &[f64]) -> f64 {
+    if velocities.is_empty() {
+        return 0.0;
+    }
+    let twice_kinetic: f64 = velocities
+        .iter()
+        .zip(masses)
+        .map(|(velocity, mass)| mass * velocity.dot(velocity))
+        .sum();
+    #[allow(clippy::cast_precision_loss)]
+    let count = velocities.len() as f64;
+    twice_kinetic / (2.0 * count)
+}
+
+/// Wraps `position` back into the periodic `cell`.
+pub fn wrap(position: Vector2<f64>, cell: Boundary) -> Vector2<f64> {
+    let size = cell.max - cell.min;
+    let offset = position - cell.min;
+    cell.min + Vector2::new(offset.x.rem_euclid(size.x), offset.y.rem_euclid(size.y))
+}
+
+/// A periodic box of `side` by `side` Lennard-Jones particles on a square lattice at `density`,
+/// with random velocities, held at `temperature` by a Langevin thermostat.
+pub fn lennard_jones_fluid(side: u32, density: f64, temperature: f64) -> Scenario {
+    let spacing = density.sqrt().recip();
+    let length = spacing * f64::from(side);
+    let cell = Boundary {
+        min: Vector2::default(),
+        max: Vector2::new(length, length),
+    };
+    let mut random = Random::new(side.into());
+    let bodies = (0..side * side)
+        .map(|i| {
+            let lattice = Vector2::new(f64::from(i % side), f64::from(i / side));
+            let position = (lattice + Vector2::new(0.5, 0.5)) * spacing;
+            let mut boid = Boid::new(position.x, position.y, 1.0);
+            let velocity = Vector2::new(random.gaussian(), random.gaussian());
+            boid.set_velocity(velocity * temperature.sqrt());
+            boid
+        })
+        .collect();
+
+    Scenario {
+        bodies,
+        parameters: Parameters::new(UnitSystem::LENNARD_JONES, DT)
+            .without_self_gravity()
+            .with_lennard_jones(LennardJones {
+                epsilon: 1.0,
+                sigma: 1.0,
+                cutoff: 2.5,
+            })
+            .with_periodic_cell(cell)
+            .with_thermostat(Thermostat::Langevin {
+                temperature,
+                friction: 1.0,
+            }),
+        period: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bodies::Bodies, quadtree::Quadtree};
+
+    const LAW: LennardJones = LennardJones {
+        epsilon: 1.0,
+        sigma: 1.0,
+        cutoff: 2.5,
+    };
+
+    #[test]
+    fn force_vanishes_at_the_bottom_of_the_well() {
+        let minimum = 2.0_f64.powf(1.0 / 6.0) * LAW.sigma;
+        assert!(LAW.magnitude(minimum, 1.0, 1.0).abs() < 1e-12);
+        // Repulsive inside the well and attractive outside it.
+        assert!(LAW.magnitude(0.99 * minimum, 1.0, 1.0) < 0.0);
+        assert!(LAW.magnitude(1.01 * minimum, 1.0, 1.0) > 0.0);
+    }
+
+    #[test]
+    fn pairs_interact_across_the_periodic_wall() {
+        let cell = Boundary {
+            min: Vector2::default(),
+            max: Vector2::new(10.0, 10.0),
+        };
+        let bodies: Bodies = [Boid::new(0.5, 5.0, 1.0), Boid::new(9.5, 5.0, 1.0)]
+            .into_iter()
+            .collect();
+        let tree = Quadtree::build(&bodies);
+        // The nearest image of the second is a unit to the left of the first.
+        let expected = LAW.force(Vector2::new(-1.0, 0.0), 1.0, 1.0);
+        assert!(expected.x > 0.0, "{expected:?}");
+        let force = LAW.calculate_force(&tree, bodies.get(0), Some(cell));
+        assert!((force - expected).magnitude() < 1e-12, "{force:?}");
+        let force = LAW.calculate_force(&tree, bodies.get(1), Some(cell));
+        assert!((force + expected).magnitude() < 1e-12, "{force:?}");
+        // Without the cell they are too far apart to feel each other.
+        let force = LAW.calculate_force(&tree, bodies.get(0), None);
+        assert_eq!(force, Vector2::default());
+    }
+
+    /// A thousand unit masses with random velocities at a temperature of about two.
+    fn hot(random: &mut Random) -> (Vec<Vector2<f64>>, Vec<f64>) {
+        let velocities = (0..1_000)
+            .map(|_| Vector2::new(random.gaussian(), random.gaussian()) * 2.0_f64.sqrt())
+            .collect();
+        (velocities, vec![1.0; 1_000])
+    }
+
+    #[test]
+    fn berendsen_relaxes_to_the_target() {
+        let mut random = Random::new(1);
+        let (mut velocities, masses) = hot(&mut random);
+        let thermostat = Thermostat::Berendsen {
+            temperature: 1.0,
+            time_constant: 0.1,
+        };
+        // Twenty time constants.
+        for _ in 0..400 {
+            thermostat.apply(&mut velocities, &masses, DT, &mut random);
+        }
+        let temperature = kinetic_temperature(&velocities, &masses);
+        assert!((temperature - 1.0).abs() < 1e-6, "{temperature}");
+    }
+
+    #[test]
+    fn langevin_relaxes_to_the_target() {
+        let mut random = Random::new(2);
+        let (mut velocities, masses) = hot(&mut random);
+        let thermostat = Thermostat::Langevin {
+            temperature: 1.0,
+            friction: 1.0,
+        };
+        // Ten friction times to relax, then the average over ten more, as it keeps fluctuating.
+        let steps = 2_000;
+        let mut total = 0.0;
+        for step in 0..2 * steps {
+            thermostat.apply(&mut velocities, &masses, DT, &mut random);
+            if step >= steps {
+                total += kinetic_temperature(&velocities, &masses);
+            }
+        }
+        let temperature = total / f64::from(steps);
+        assert!((temperature - 1.0).abs() < 0.05, "{temperature}");
+    }
+}
@@ -0,0 +1,50 @@
+//! Morton (Z-order) keys, which order bodies so that ones close together in space are also close
+//! together in memory. Contiguous runs of sorted bodies make up the nodes of the quadtree, so it
+//! can be built bottom up and split into compact pieces of work.
+
+use crate::{boundary::Boundary, traits::Position, vector::Vector2};
+
+/// Bits of each coordinate in a key, so that a key fits in a `u64`.
+pub const BITS: u32 = 32;
+
+/// Spreads the bits of `value` out to every other bit.
+fn spread(value: u32) -> u64 {
+    let mut bits = u64::from(value);
+    bits = (bits | (bits << 16)) & 0x0000_FFFF_0000_FFFF;
+    bits = (bits | (bits << 8)) & 0x00FF_00FF_00FF_00FF;
+    bits = (bits | (bits << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    bits = (bits | (bits << 2)) & 0x3333_3333_3333_3333;
+    (bits | (bits << 1)) & 0x5555_5555_5555_5555
+}
+
+/// Where `value` falls between `min` and `max`, as one of `2^BITS` steps.
+fn quantise(value: f64, min: f64, max: f64) -> u32 {
+    let steps = f64::from(u32::MAX) + 1.0;
+    if max <= min {
+        return 0;
+    }
+    let step = ((value - min) / (max - min) * steps).clamp(0.0, steps - 1.0);
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let step = step as u32;
+    step
+}
+
+/// Key of `position` within `bounds`. Each pair of bits, from the top, is the quadrant the
+/// quadtree puts it in at the next level down, with the x bit above the y bit.
+pub fn key(position: Vector2<f64>, bounds: Boundary) -> u64 {
+    let x = quantise(position.x, bounds.min.x, bounds.max.x);
+    let y = quantise(position.y, bounds.min.y, bounds.max.y);
+    (spread(x) << 1) | spread(y)
+}
+
+/// Sorts `bodies` along the Z-order curve through `bounds`.
+pub fn sort<T: Position>(bodies: &mut [T], bounds: Boundary) {
+    bodies.sort_by_cached_key(|body| key(body.position(), bounds));
+}
+
+/// Indices of `positions` in the order [`sort`] would put them in.
+pub fn order(positions: &[Vector2<f64>], bounds: Boundary) -> Vec<usize> {
+    let mut order: Vec<_> = (0..positions.len()).collect();
+    order.sort_by_cached_key(|&index| key(positions[index], bounds));
+    order
+}
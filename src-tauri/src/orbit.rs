@@ -0,0 +1,518 @@
+use std::f64::consts::{PI, TAU};
+
+use crate::{bodies::Bodies, vector::Vector2};
+
+const KEPLER_TOLERANCE: f64 = 1e-14;
+const KEPLER_MAX_ITERATIONS: usize = 64;
+/// Eccentricities below this are treated as circular when recovering elements.
+const CIRCULAR_TOLERANCE: f64 = 1e-12;
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+pub enum OrbitError {
+    #[error("eccentricity {0} is not a valid conic section")]
+    InvalidEccentricity(f64),
+    #[error("parabolic orbits (eccentricity of exactly 1) are not supported")]
+    Parabolic,
+    #[error("the orbit is degenerate: the body is at or moving directly through the primary")]
+    Degenerate,
+}
+
+/// Which way round the primary the body travels, as seen looking down on the plane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum Direction {
+    /// Anticlockwise, the same sense as a positive angle.
+    Prograde,
+    /// Clockwise.
+    Retrograde,
+}
+
+impl Direction {
+    fn sign(self) -> f64 {
+        match self {
+            Self::Prograde => 1.0,
+            Self::Retrograde => -1.0,
+        }
+    }
+}
+
+/// Position and velocity of a body relative to its primary.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StateVector {
+    pub position: Vector2<f64>,
+    pub velocity: Vector2<f64>,
+}
+
+/// Keplerian elements of an orbit confined to the plane.
+///
+/// Bound orbits have `eccentricity < 1` and a positive `semi_major_axis`. Hyperbolic orbits have
+/// `eccentricity > 1` and, by convention, a negative `semi_major_axis`.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct OrbitalElements {
+    pub semi_major_axis: f64,
+    pub eccentricity: f64,
+    /// Angle of the periapsis from the positive x axis, in radians.
+    pub argument_of_periapsis: f64,
+    /// Mean anomaly at the epoch, in radians. For hyperbolic orbits this is the hyperbolic mean
+    /// anomaly and is not wrapped.
+    pub mean_anomaly: f64,
+    pub direction: Direction,
+}
+
+impl OrbitalElements {
+    pub fn new(
+        semi_major_axis: f64,
+        eccentricity: f64,
+        argument_of_periapsis: f64,
+        mean_anomaly: f64,
+    ) -> Self {
+        Self {
+            semi_major_axis,
+            eccentricity,
+            argument_of_periapsis,
+            mean_anomaly,
+            direction: Direction::Prograde,
+        }
+    }
+
+    /// A prograde circular orbit, with the body `phase` radians round from the positive x axis.
+    pub fn circular(radius: f64, phase: f64) -> Self {
+        Self::new(radius, 0.0, 0.0, phase)
+    }
+
+    #[must_use]
+    pub fn retrograde(mut self) -> Self {
+        self.direction = Direction::Retrograde;
+        self
+    }
+
+    pub fn is_bound(&self) -> bool {
+        self.eccentricity < 1.0
+    }
+
+    /// Mean motion for a primary with gravitational parameter `mu`.
+    pub fn mean_motion(&self, mu: f64) -> f64 {
+        (mu / self.semi_major_axis.abs().powi(3)).sqrt()
+    }
+
+    /// Orbital period for a primary with gravitational parameter `mu`, if the orbit is bound.
+    pub fn period(&self, mu: f64) -> Option<f64> {
+        self.is_bound().then(|| TAU / self.mean_motion(mu))
+    }
+
+    /// Closest distance to the primary.
+    pub fn periapsis(&self) -> f64 {
+        self.semi_major_axis * (1.0 - self.eccentricity)
+    }
+
+    /// Apoapsis distance, if the orbit is bound.
+    pub fn apoapsis(&self) -> Option<f64> {
+        self.is_bound()
+            .then_some(self.semi_major_axis * (1.0 + self.eccentricity))
+    }
+
+    /// Converts the elements to a position and velocity relative to a primary with gravitational
+    /// parameter `mu` (`G * (m_primary + m_body)`).
+    pub fn to_state_vector(self, mu: f64) -> Result<StateVector, OrbitError> {
+        let a = self.semi_major_axis;
+        let e = self.eccentricity;
+        let n = self.mean_motion(mu);
+
+        let (position, velocity) = if e < 0.0 || !e.is_finite() {
+            return Err(OrbitError::InvalidEccentricity(e));
+        } else if e < 1.0 {
+            let anomaly = solve_kepler(self.mean_anomaly, e)?;
+            let (sin, cos) = anomaly.sin_cos();
+            let b = a * (1.0 - e * e).sqrt();
+            let rate = n / (1.0 - e * cos);
+            (
+                Vector2::new(a * (cos - e), b * sin),
+                Vector2::new(-a * sin * rate, b * cos * rate),
+            )
+        } else if e > 1.0 {
+            let anomaly = solve_hyperbolic_kepler(self.mean_anomaly, e)?;
+            let (sinh, cosh) = (anomaly.sinh(), anomaly.cosh());
+            let a = a.abs();
+            let b = a * (e * e - 1.0).sqrt();
+            let rate = n / (e * cosh - 1.0);
+            (
+                Vector2::new(a * (e - cosh), b * sinh),
+                Vector2::new(-a * sinh * rate, b * cosh * rate),
+            )
+        } else {
+            return Err(OrbitError::Parabolic);
+        };
+
+        let sign = self.direction.sign();
+        Ok(StateVector {
+            position: Vector2::new(position.x, sign * position.y)
+                .rotate(self.argument_of_periapsis),
+            velocity: Vector2::new(velocity.x, sign * velocity.y)
+                .rotate(self.argument_of_periapsis),
+        })
+    }
+
+    /// Recovers the elements of the orbit through `state`, relative to a primary with gravitational
+    /// parameter `mu`.
+    pub fn from_state_vector(state: StateVector, mu: f64) -> Result<Self, OrbitError> {
+        let StateVector { position, velocity } = state;
+        let r = position.magnitude();
+        let angular_momentum = position.cross(&velocity);
+        if r == 0.0 || angular_momentum == 0.0 {
+            return Err(OrbitError::Degenerate);
+        }
+        let direction = if angular_momentum > 0.0 {
+            Direction::Prograde
+        } else {
+            Direction::Retrograde
+        };
+        let sign = direction.sign();
+
+        let speed_squared = velocity.dot(&velocity);
+        let energy = speed_squared / 2.0 - mu / r;
+        let eccentricity_vector =
+            (position * (speed_squared - mu / r) - velocity * position.dot(&velocity)) / mu;
+        let e = eccentricity_vector.magnitude();
+
+        // A circular orbit has no periapsis, so measure the anomaly from the x axis instead.
+        let argument_of_periapsis = if e > CIRCULAR_TOLERANCE {
+            eccentricity_vector.angle()
+        } else {
+            0.0
+        };
+        let true_anomaly = sign * (position.angle() - argument_of_periapsis);
+        let (sin_nu, cos_nu) = true_anomaly.sin_cos();
+
+        let mean_anomaly = if e < 1.0 {
+            let anomaly = ((1.0 - e * e).sqrt() * sin_nu).atan2(e + cos_nu);
+            (anomaly - e * anomaly.sin()).rem_euclid(TAU)
+        } else if e > 1.0 {
+            let anomaly = ((e * e - 1.0).sqrt() * sin_nu / (1.0 + e * cos_nu)).asinh();
+            e * anomaly.sinh() - anomaly
+        } else {
+            return Err(OrbitError::Parabolic);
+        };
+
+        Ok(Self {
+            semi_major_axis: -mu / (2.0 * energy),
+            eccentricity: e,
+            argument_of_periapsis: argument_of_periapsis.rem_euclid(TAU),
+            mean_anomaly,
+            direction,
+        })
+    }
+}
+
+/// Solves Kepler's equation `M = E - e sin E` for the eccentric anomaly `E` of an elliptical orbit.
+///
+/// Newton's method is safeguarded by bisection, so it converges for every eccentricity in `[0, 1)`
+/// including the near-parabolic case where a bare Newton iteration can overshoot.
+pub fn solve_kepler(mean_anomaly: f64, eccentricity: f64) -> Result<f64, OrbitError> {
+    if !(0.0..1.0).contains(&eccentricity) {
+        return Err(OrbitError::InvalidEccentricity(eccentricity));
+    }
+    // Reduce to [0, pi] using the symmetry of the equation, solve there, then undo the reduction.
+    let wrapped = (mean_anomaly + PI).rem_euclid(TAU) - PI;
+    let (sign, m) = if wrapped < 0.0 {
+        (-1.0, -wrapped)
+    } else {
+        (1.0, wrapped)
+    };
+    let turns = mean_anomaly - wrapped;
+
+    let f = |anomaly: f64| anomaly - eccentricity * anomaly.sin() - m;
+    let df = |anomaly: f64| 1.0 - eccentricity * anomaly.cos();
+
+    // On [0, pi] the root always lies in [M, M + e].
+    let mut low = m;
+    let mut high = (m + eccentricity).min(PI);
+    let mut anomaly = if eccentricity > 0.8 {
+        PI.min(high)
+    } else {
+        m + eccentricity * m.sin()
+    };
+    let anomaly = safeguarded_newton(f, df, &mut low, &mut high, &mut anomaly);
+
+    Ok(turns + sign * anomaly)
+}
+
+/// Solves the hyperbolic Kepler equation `M = e sinh H - H` for the hyperbolic anomaly `H`.
+pub fn solve_hyperbolic_kepler(mean_anomaly: f64, eccentricity: f64) -> Result<f64, OrbitError> {
+    if eccentricity <= 1.0 || !eccentricity.is_finite() {
+        return Err(OrbitError::InvalidEccentricity(eccentricity));
+    }
+    let m = mean_anomaly.abs();
+
+    let f = |anomaly: f64| eccentricity * anomaly.sinh() - anomaly - m;
+    let df = |anomaly: f64| eccentricity * anomaly.cosh() - 1.0;
+
+    // f is increasing and convex on [0, inf), with f(0) <= 0 and f(asinh(M / (e - 1))) >= 0.
+    let mut low = 0.0;
+    let mut high = (m / (eccentricity - 1.0)).asinh();
+    let mut anomaly = (2.0 * m / eccentricity + 1.8).ln().clamp(low, high);
+    let anomaly = safeguarded_newton(f, df, &mut low, &mut high, &mut anomaly);
+
+    Ok(anomaly.copysign(mean_anomaly))
+}
+
+/// Newton iteration for an increasing function, falling back to bisection of `[low, high]` whenever
+/// a step would leave the bracket.
+fn safeguarded_newton(
+    f: impl Fn(f64) -> f64,
+    df: impl Fn(f64) -> f64,
+    low: &mut f64,
+    high: &mut f64,
+    x: &mut f64,
+) -> f64 {
+    for _ in 0..KEPLER_MAX_ITERATIONS {
+        let value = f(*x);
+        if value.abs() <= KEPLER_TOLERANCE * x.abs().max(1.0) {
+            break;
+        }
+        if value < 0.0 {
+            *low = *x;
+        } else {
+            *high = *x;
+        }
+        let step = *x - value / df(*x);
+        *x = if step > *low && step < *high {
+            step
+        } else {
+            *low + (*high - *low) / 2.0
+        };
+        if *high - *low <= f64::EPSILON * x.abs().max(1.0) {
+            break;
+        }
+    }
+    *x
+}
+
+/// The body an orbit is measured against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum Attractor {
+    /// Index of the attracting body, or its id once sent to the frontend.
+    Body(usize),
+    /// The centre of mass of the whole system, used when no body dominates.
+    Barycentre,
+}
+
+/// Radius of the Hill sphere of a body of `mass` a `distance` away from a primary of
+/// `primary_mass`.
+pub fn hill_radius(distance: f64, mass: f64, primary_mass: f64) -> f64 {
+    distance * (mass / (3.0 * primary_mass)).cbrt()
+}
+
+/// The dominant attractor of every body in `system`: the nearest heavier body whose Hill sphere
+/// contains it, or the barycentre if there is none.
+///
+/// Bodies are visited heaviest first so each Hill sphere is measured against the body's own
+/// attractor, which puts a moon in its planet's sphere rather than the star's. The heaviest body
+/// dominates the whole system, so its sphere is unbounded, and bodies orbiting the barycentre are
+/// measured against the total mass of the others.
+pub fn dominant_attractors(system: &Bodies) -> Vec<Attractor> {
+    let (masses, positions) = (system.masses(), system.positions());
+    let total_mass: f64 = masses.iter().sum();
+    let barycentre = positions
+        .iter()
+        .zip(masses)
+        .fold(Vector2::default(), |sum, (position, mass)| {
+            sum + *position * *mass
+        })
+        / total_mass;
+    let mut order: Vec<usize> = (0..system.len()).collect();
+    order.sort_by(|a, b| masses[*b].total_cmp(&masses[*a]));
+
+    let mut attractors = vec![Attractor::Barycentre; system.len()];
+    let mut hill_radii = vec![0.0; system.len()];
+    for (rank, &body) in order.iter().enumerate() {
+        let nearest = order[..rank]
+            .iter()
+            .filter(|&&other| masses[other] > masses[body])
+            .map(|&other| (other, (positions[other] - positions[body]).magnitude()))
+            .filter(|&(other, distance)| distance < hill_radii[other])
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        hill_radii[body] = match nearest {
+            Some((other, distance)) => {
+                attractors[body] = Attractor::Body(other);
+                hill_radius(distance, masses[body], masses[other])
+            }
+            None if rank == 0 => f64::INFINITY,
+            None => hill_radius(
+                (positions[body] - barycentre).magnitude(),
+                masses[body],
+                total_mass,
+            ),
+        };
+    }
+    attractors
+}
+
+/// A body's osculating orbit about its dominant attractor.
+#[derive(Debug, Clone, Copy)]
+pub struct OsculatingOrbit {
+    pub attractor: Attractor,
+    /// Gravitational parameter the elements are measured with, which also gives their period.
+    pub mu: f64,
+    /// `None` when the instantaneous orbit is degenerate or parabolic.
+    pub elements: Option<OrbitalElements>,
+}
+
+/// Osculating orbit of every body in `system` relative to its dominant attractor.
+pub fn osculating_elements(system: &Bodies, gravity: f64) -> Vec<OsculatingOrbit> {
+    let attractors = dominant_attractors(system);
+    let total_mass: f64 = system.masses().iter().sum();
+    let barycentre = StateVector {
+        position: system.iter().fold(Vector2::default(), |sum, body| {
+            sum + body.position() * body.mass()
+        }) / total_mass,
+        velocity: system.iter().fold(Vector2::default(), |sum, body| {
+            sum + body.velocity() * body.mass()
+        }) / total_mass,
+    };
+
+    (0..system.len())
+        .map(|i| {
+            let subject = system.get(i);
+            let attractor = attractors[i];
+            let (primary, mu) = match attractor {
+                Attractor::Body(j) => (
+                    StateVector {
+                        position: system.get(j).position(),
+                        velocity: system.get(j).velocity(),
+                    },
+                    gravity * (system.get(j).mass() + subject.mass()),
+                ),
+                // The rest of the system is (M - m) / M as far from the barycentre as from the
+                // body, so seen from the barycentre its mass pulls as if it were (M - m)^3 / M^2.
+                Attractor::Barycentre => {
+                    let others = total_mass - subject.mass();
+                    (barycentre, gravity * others.powi(3) / total_mass.powi(2))
+                }
+            };
+            let relative = StateVector {
+                position: subject.position() - primary.position,
+                velocity: subject.velocity() - primary.velocity,
+            };
+            OsculatingOrbit {
+                attractor,
+                mu,
+                elements: OrbitalElements::from_state_vector(relative, mu).ok(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boid::Boid;
+
+    #[test]
+    fn kepler_solutions_satisfy_the_equation() {
+        for eccentricity in [0.0, 0.1, 0.5, 0.9, 0.99, 0.999_999] {
+            for step in -40..=40 {
+                let mean_anomaly = f64::from(step) * 0.25;
+                let anomaly = solve_kepler(mean_anomaly, eccentricity).unwrap();
+                let residual = anomaly - eccentricity * anomaly.sin() - mean_anomaly;
+                assert!(residual.abs() < 1e-12, "e {eccentricity} M {mean_anomaly}");
+            }
+        }
+        assert!(solve_kepler(1.0, 1.0).is_err());
+        assert!(solve_kepler(1.0, -0.1).is_err());
+    }
+
+    #[test]
+    fn hyperbolic_kepler_solutions_satisfy_the_equation() {
+        for eccentricity in [1.000_1, 1.5, 3.0, 100.0] {
+            for step in -40..=40 {
+                let mean_anomaly = f64::from(step) * 1.25;
+                let anomaly = solve_hyperbolic_kepler(mean_anomaly, eccentricity).unwrap();
+                let residual = eccentricity * anomaly.sinh() - anomaly - mean_anomaly;
+                assert!(
+                    residual.abs() < 1e-10 * mean_anomaly.abs().max(1.0),
+                    "e {eccentricity} M {mean_anomaly}"
+                );
+            }
+        }
+        assert!(solve_hyperbolic_kepler(1.0, 1.0).is_err());
+        assert!(solve_hyperbolic_kepler(1.0, f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn state_vectors_round_trip() {
+        let mu = 3.0;
+        let mut worst: f64 = 0.0;
+        for eccentricity in [0.0, 0.3, 0.9, 1.5, 4.0] {
+            let semi_major_axis = if eccentricity < 1.0 { 2.0 } else { -2.0 };
+            for step in 0..12 {
+                let angle = f64::from(step) * 0.5;
+                for elements in [
+                    OrbitalElements::new(semi_major_axis, eccentricity, angle, angle - 3.0),
+                    OrbitalElements::new(semi_major_axis, eccentricity, -angle, 1.0).retrograde(),
+                ] {
+                    let state = elements.to_state_vector(mu).unwrap();
+                    let recovered = OrbitalElements::from_state_vector(state, mu).unwrap();
+                    assert_eq!(recovered.direction, elements.direction);
+                    let again = recovered.to_state_vector(mu).unwrap();
+                    let error = (again.position - state.position).magnitude()
+                        / state.position.magnitude()
+                        + (again.velocity - state.velocity).magnitude()
+                            / state.velocity.magnitude();
+                    worst = worst.max(error);
+                }
+            }
+        }
+        assert!(worst < 1e-7, "{worst}");
+    }
+
+    #[test]
+    fn apsides_are_the_extreme_distances() {
+        let elements = OrbitalElements::new(2.0, 0.5, 1.0, 0.0);
+        let closest = elements.to_state_vector(1.0).unwrap().position.magnitude();
+        assert!((closest - elements.periapsis()).abs() < 1e-12);
+        let farthest = OrbitalElements {
+            mean_anomaly: PI,
+            ..elements
+        }
+        .to_state_vector(1.0)
+        .unwrap()
+        .position
+        .magnitude();
+        assert!((farthest - elements.apoapsis().unwrap()).abs() < 1e-12);
+
+        let hyperbolic = OrbitalElements::new(-2.0, 1.5, 0.0, 0.0);
+        let closest = hyperbolic
+            .to_state_vector(1.0)
+            .unwrap()
+            .position
+            .magnitude();
+        assert!((closest - hyperbolic.periapsis()).abs() < 1e-12);
+        assert_eq!(hyperbolic.apoapsis(), None);
+    }
+
+    #[test]
+    fn barycentric_orbits_report_their_period() {
+        // A binary of masses 3 and 1, a distance 2 apart, circling each other once every
+        // 2π sqrt(d^3 / G M) = 2π sqrt(2).
+        let period = TAU * 2.0_f64.sqrt();
+        let speed = TAU * 2.0 / period;
+        let mut heavy = Boid::new(-0.5, 0.0, 3.0);
+        heavy.set_velocity(Vector2::new(0.0, -speed / 4.0));
+        let mut light = Boid::new(1.5, 0.0, 1.0);
+        light.set_velocity(Vector2::new(0.0, speed * 3.0 / 4.0));
+        let system: Bodies = [heavy, light].into_iter().collect();
+
+        let orbits = osculating_elements(&system, 1.0);
+        assert_eq!(orbits[0].attractor, Attractor::Barycentre);
+        assert_eq!(orbits[1].attractor, Attractor::Body(0));
+        for orbit in orbits {
+            let elements = orbit.elements.unwrap();
+            assert!(elements.eccentricity < 1e-12, "{elements:?}");
+            let reported = elements.period(orbit.mu).unwrap();
+            assert!(
+                (reported - period).abs() < 1e-12,
+                "{reported} against {period}"
+            );
+        }
+    }
+}
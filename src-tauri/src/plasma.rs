@@ -0,0 +1,158 @@
+//! Charged-body scenarios, run in N-body units with Coulomb interactions on top of gravity, and in
+//! external electric and magnetic fields.
+
+use std::f64::consts::TAU;
+
+use crate::{
+    boid::Boid,
+    field::{ElectricField, MagneticField},
+    force_law::Coulomb,
+    integrator::Integrator,
+    scenario::{Parameters, Scenario},
+    units::UnitSystem,
+    vector::Vector2,
+};
+
+const DT: f64 = 1e-3;
+
+fn charged(position: Vector2<f64>, mass: f64, charge: f64) -> Boid {
+    let mut boid = Boid::new(position.x, position.y, mass);
+    boid.set_charge(charge);
+    boid
+}
+
+/// A disc of alternating positive and negative charges on a square lattice, starting at rest.
+///
+/// Coulomb's constant is a hundred times `G`, so electrostatics dominate and opposite charges pair
+/// up before the blob as a whole starts to collapse.
+pub fn plasma_blob(radius: f64) -> Scenario {
+    let mut bodies = Vec::new();
+    let mut y = -radius;
+    while y <= radius {
+        let mut x = -radius;
+        while x <= radius {
+            let position = Vector2::new(x, y);
+            if position.magnitude() <= radius {
+                // A checkerboard keeps the blob neutral with every neighbour opposite.
+                let sign = if (x + y).rem_euclid(2.0) < 1.0 {
+                    1.0
+                } else {
+                    -1.0
+                };
+                bodies.push(charged(position, 1.0, sign));
+            }
+            x += 1.0;
+        }
+        y += 1.0;
+    }
+
+    Scenario {
+        bodies,
+        parameters: Parameters::new(UnitSystem::NBODY, DT).with_electrostatics(Coulomb {
+            constant: 100.0,
+            softening: 0.1,
+        }),
+        period: None,
+    }
+}
+
+/// Rings of like-charged ions orbiting a heavy neutral core, whose gravity confines them while
+/// their mutual repulsion keeps them spaced out.
+pub fn ion_crystal(rings: u32) -> Scenario {
+    const CORE_MASS: f64 = 1000.0;
+    let core = charged(Vector2::default(), CORE_MASS, 0.0);
+    let mut bodies = vec![core];
+
+    for ring in 1..=rings {
+        let radius = f64::from(ring);
+        let count = 6 * ring;
+        // Ignores the ions' own repulsion, so the rings breathe a little as they settle.
+        let speed = (CORE_MASS / radius).sqrt();
+        for i in 0..count {
+            let angle = TAU * f64::from(i) / f64::from(count);
+            let position = Vector2::new(radius, 0.0).rotate(angle);
+            let mut ion = charged(position, 1.0, 1.0);
+            ion.set_velocity(Vector2::new(-position.y, position.x) * (speed / radius));
+            bodies.push(ion);
+        }
+    }
+
+    Scenario {
+        bodies,
+        parameters: Parameters::new(UnitSystem::NBODY, DT).with_electrostatics(Coulomb {
+            constant: 1.0,
+            softening: 0.01,
+        }),
+        period: None,
+    }
+}
+
+/// Mass of the test particles in the field scenarios. It is small enough that their gravity on
+/// each other is negligible, with charges to match so each has a charge to mass ratio near one.
+const TEST_MASS: f64 = 1e-3;
+
+/// Particles of both signs and several charge to mass ratios, starting at rest in crossed uniform
+/// fields.
+///
+/// Each gyrates with its own radius and sense, but they all drift together at `E x B / B^2`, here
+/// half a unit per unit time towards negative y, which is up the screen.
+pub fn exb_drift() -> Scenario {
+    let bodies = [1.0, -1.0, 2.0, -2.0, 4.0, -4.0]
+        .iter()
+        .zip(0_u32..)
+        .map(|(ratio, i)| {
+            let position = Vector2::new(3.0 * f64::from(i), 0.0);
+            charged(position, TEST_MASS, ratio * TEST_MASS)
+        })
+        .collect();
+
+    Scenario {
+        bodies,
+        parameters: Parameters::new(UnitSystem::NBODY, DT)
+            .with_magnetic_field(MagneticField::Uniform { strength: 1.0 })
+            .with_electric_field(ElectricField::Uniform {
+                field: Vector2::new(0.5, 0.0),
+            })
+            .with_integrator(Integrator::Boris),
+        period: None,
+    }
+}
+
+/// A ring of ions in the radial plane of a Penning trap, held in by a strong magnetic field against
+/// the trap's outward electric field and their own repulsion.
+pub fn penning_trap(ions: u32) -> Scenario {
+    const MAGNETIC_FIELD: f64 = 10.0;
+    const QUADRUPOLE: f64 = 5.0;
+    // For a charge to mass ratio of one the cyclotron frequency is just the field, and the slow
+    // magnetron frequency follows from it and the quadrupole strength.
+    let magnetron =
+        (MAGNETIC_FIELD - (MAGNETIC_FIELD * MAGNETIC_FIELD - 4.0 * QUADRUPOLE).sqrt()) / 2.0;
+
+    let bodies = (0..ions)
+        .map(|i| {
+            let position = Vector2::new(1.0, 0.0).rotate(TAU * f64::from(i) / f64::from(ions));
+            let mut ion = charged(position, TEST_MASS, TEST_MASS);
+            // The magnetron motion is an E x B drift, clockwise for an outward field.
+            ion.set_velocity(Vector2::new(position.y, -position.x) * magnetron);
+            ion
+        })
+        .collect();
+
+    Scenario {
+        bodies,
+        parameters: Parameters::new(UnitSystem::NBODY, DT)
+            .with_electrostatics(Coulomb {
+                constant: 1000.0,
+                softening: 0.01,
+            })
+            .with_magnetic_field(MagneticField::Uniform {
+                strength: MAGNETIC_FIELD,
+            })
+            .with_electric_field(ElectricField::Quadrupole {
+                strength: QUADRUPOLE,
+                center: Vector2::default(),
+            })
+            .with_integrator(Integrator::Boris),
+        period: None,
+    }
+}
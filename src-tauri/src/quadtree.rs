@@ -1,13 +1,25 @@
+use std::collections::HashMap;
+use std::thread;
+
 use crate::{
-    boid::Boid,
+    bodies::{Bodies, BodyRef, Particle},
     boundary::Boundary,
-    traits::{Intersect, Mass},
-    types::BoidRCell,
+    force_law::{ExpansionOrder, ForceLaw},
+    morton,
+    spatial::{self, MassGroup, Nearest, SpatialIndex},
+    traits::{Intersect, Mass, Position},
     vector::Vector2,
-    Body, GRAVITY,
+    vortex::PointVortices,
+    Body,
 };
 
-type Child = Box<Node>;
+type Child<T> = Box<Node<T>>;
+
+/// Levels of the tree whose subtrees are built concurrently, giving up to `4^PARALLEL_LEVELS`
+/// threads.
+const PARALLEL_LEVELS: u32 = 2;
+/// Fewest bodies in a node worth spreading its subtrees over threads.
+const PARALLEL_BODIES: usize = 4096;
 
 type InsertionResult = Result<(), InsertionError>;
 
@@ -20,33 +32,185 @@ pub enum InsertionError {
     },
 }
 
+/// Anything a [`Quadtree`] can hold. Beyond a mass and a position, items may carry a charge and a
+/// circulation for the electrostatic and vortex walks, and their acceleration on the previous step
+/// for [`OpeningCriterion::Relative`]. Each is zero unless the item has one.
+pub trait Item: Mass + Position + Clone + Send + Sync {
+    fn charge(&self) -> f64 {
+        0.0
+    }
+
+    fn circulation(&self) -> f64 {
+        0.0
+    }
+
+    fn acceleration(&self) -> Vector2<f64> {
+        Vector2::default()
+    }
+}
+
+/// When a node is far enough from a body to stand in for the bodies inside it.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum OpeningCriterion {
+    /// Barnes–Hut: the node's width over the distance to its centre of mass is below `theta`.
+    Geometric { theta: f64 },
+    /// Salmon–Warren: as [`Self::Geometric`], but with `b_max`, the distance from the centre of
+    /// mass to the farthest corner, in place of the width. Nodes whose mass sits off to one side
+    /// are then opened sooner on the side where the approximation is worst.
+    SalmonWarren { theta: f64 },
+    /// Gadget's relative criterion: the estimated error of the expansion, `G M w² / r⁴`, must be
+    /// below `tolerance` times the body's acceleration on the previous step. Until there is one,
+    /// it falls back on the geometric test with `theta`.
+    Relative { tolerance: f64, theta: f64 },
+}
+
+impl OpeningCriterion {
+    pub const fn theta(self) -> f64 {
+        match self {
+            Self::Geometric { theta }
+            | Self::SalmonWarren { theta }
+            | Self::Relative { theta, .. } => theta,
+        }
+    }
+
+    /// The same criterion with opening angle `theta`. The relative criterion only uses it until
+    /// bodies have an acceleration to compare against.
+    #[must_use]
+    pub const fn with_theta(self, theta: f64) -> Self {
+        match self {
+            Self::Geometric { .. } => Self::Geometric { theta },
+            Self::SalmonWarren { .. } => Self::SalmonWarren { theta },
+            Self::Relative { tolerance, .. } => Self::Relative { tolerance, theta },
+        }
+    }
+}
+
+/// A source in an interaction list: a single body, or the expansion of a far away node.
+#[derive(Debug, Clone, Copy)]
+struct Interaction {
+    position: Vector2<f64>,
+    mass: f64,
+    /// Second moments about `position` when the quadrupole is used.
+    moment: Option<[f64; 3]>,
+}
+
+/// Forces from a group walk, looked up by body index.
+pub struct GroupForces(HashMap<usize, Vector2<f64>>);
+
+impl GroupForces {
+    /// Force on `body`, or zero for a body that is not in the tree.
+    pub fn get(&self, body: BodyRef) -> Vector2<f64> {
+        self.0.get(&body.index()).copied().unwrap_or_default()
+    }
+}
+
+/// Smallest and largest opening angles that [`adapt_opening_angle`] moves between.
+const THETA_RANGE: (f64, f64) = (0.1, 1.5);
+
+/// Opening angle expected to bring a walk with `relative_error` at `theta` to `accuracy`, given
+/// that the error scales with `theta` to the truncation power of `order`. Each step changes it by
+/// at most a factor of two, so that one poor estimate cannot throw it far off.
+pub fn adapt_opening_angle(
+    theta: f64,
+    relative_error: f64,
+    accuracy: f64,
+    order: ExpansionOrder,
+) -> f64 {
+    let factor = if relative_error > 0.0 {
+        (accuracy / relative_error)
+            .powf(1.0 / f64::from(order.truncation_power()))
+            .clamp(0.5, 2.0)
+    } else {
+        // Every node was opened down to single bodies, so the angle can only have been too small.
+        2.0
+    };
+    (theta * factor).clamp(THETA_RANGE.0, THETA_RANGE.1)
+}
+
 #[derive(Debug, Clone)]
-enum Contents {
+enum Contents<T> {
     Empty,
-    Boid(BoidRCell),
-    Children([Child; 4]),
+    Body(T),
+    /// Bodies in a node at the deepest level, which the tree cannot separate any further, such as
+    /// ones at the same position.
+    Bucket(Vec<T>),
+    Children([Child<T>; 4]),
+}
+
+/// Total of one sign of a signed quantity, such as charge, within a node, and where it is centred.
+#[derive(Debug, Clone, Copy, Default)]
+struct Monopole {
+    strength: f64,
+    center: Vector2<f64>,
+}
+
+impl Monopole {
+    fn add(&mut self, strength: f64, position: Vector2<f64>) {
+        let new_strength = self.strength + strength;
+        self.center = (self.center * self.strength + position * strength) / new_strength;
+        self.strength = new_strength;
+    }
+}
+
+/// A signed quantity aggregated separately by sign, since a single monopole of mixed signs can sit
+/// far from any actual source, or vanish while the node still has a strong field.
+#[derive(Debug, Clone, Copy, Default)]
+struct SignedMonopoles {
+    positive: Monopole,
+    negative: Monopole,
+}
+
+impl SignedMonopoles {
+    fn add(&mut self, strength: f64, position: Vector2<f64>) {
+        if strength > 0.0 {
+            self.positive.add(strength, position);
+        } else if strength < 0.0 {
+            self.negative.add(strength, position);
+        }
+    }
+
+    /// The nonzero monopoles.
+    fn iter(&self) -> impl Iterator<Item = &Monopole> {
+        [&self.positive, &self.negative]
+            .into_iter()
+            .filter(|monopole| monopole.strength != 0.0)
+    }
 }
 
 #[derive(Debug, Clone)]
-struct Node {
+pub struct Node<T = Particle> {
     boundary: Boundary,
     center_of_mass: Vector2<f64>,
     mass: f64,
-    contents: Contents,
+    /// Second moments of mass about the centre of the boundary, as `[xx, xy, yy]`. Taking them
+    /// about a fixed point lets each body be added as it is inserted, and keeping that point
+    /// inside the node avoids cancellation when they are moved to the centre of mass.
+    second_moment: [f64; 3],
+    /// Number of bodies in the node.
+    bodies: usize,
+    charge: SignedMonopoles,
+    circulation: SignedMonopoles,
+    contents: Contents<T>,
 }
 
-impl Node {
+impl<T: Item> Node<T> {
     pub fn new(boundary: Boundary) -> Self {
         Self {
             boundary,
             center_of_mass: boundary.center(),
             mass: 0.0,
+            second_moment: [0.0; 3],
+            bodies: 0,
+            charge: SignedMonopoles::default(),
+            circulation: SignedMonopoles::default(),
             contents: Contents::Empty,
         }
     }
 
-    pub fn insert(&mut self, boid: BoidRCell) -> InsertionResult {
-        if !self.boundary.intersects(&boid) {
+    /// Inserts `boid` into the node at `level` below the root. Below [`morton::BITS`] levels,
+    /// bodies share a leaf rather than being split apart.
+    pub fn insert(&mut self, boid: T, level: u32) -> InsertionResult {
+        if !self.boundary.intersects(&boid.position()) {
             return Err(InsertionError::OutOfBounds {
                 boundary: self.boundary,
                 position: boid.position(),
@@ -55,96 +219,670 @@ impl Node {
 
         match &mut self.contents {
             Contents::Empty => {
-                self.update_com(&boid);
-                self.contents = Contents::Boid(boid);
+                self.update_aggregates(&boid);
+                self.contents = Contents::Body(boid);
                 Ok(())
             }
-            Contents::Boid(current_boid) => {
-                let per_new_part = (self.boundary.max - self.boundary.min) / 2.0;
-                let boundary_min = self.boundary.min;
-
-                let new_children: [Child; 4] = std::array::from_fn(|i| {
-                    let (x, y) = match i {
-                        0 => Some((0, 0)),
-                        1 => Some((0, 1)),
-                        2 => Some((1, 0)),
-                        3 => Some((1, 1)),
-                        _ => None,
-                    }
-                    .expect("Somehow the integer has exceeded 3");
-                    let offset =
-                        Vector2::new(f64::from(x) * per_new_part.x, f64::from(y) * per_new_part.y);
-                    let min = boundary_min + offset;
-                    let max = min + per_new_part;
-                    Box::new(Node::new(Boundary { min, max }))
-                });
-                let old_boid = current_boid.clone();
+            Contents::Body(current_boid) if level >= morton::BITS => {
+                let bucket = vec![current_boid.clone(), boid.clone()];
+                self.contents = Contents::Bucket(bucket);
+                self.update_aggregates(&boid);
+                Ok(())
+            }
+            Contents::Body(current_boid) => {
+                let mut new_children = Self::split(self.boundary);
+                // The old body is already counted in this node's aggregates, so it goes straight
+                // into the children.
+                Self::insert_into_children(&mut new_children, current_boid, level + 1)?;
+                Self::insert_into_children(&mut new_children, &boid, level + 1)?;
                 self.contents = Contents::Children(new_children);
-                if let Err(e) = self.insert(old_boid.clone()) {
-                    self.contents = Contents::Boid(old_boid);
-                    return Err(e);
-                }
-                self.insert(boid.clone())?;
-                self.update_com(&boid);
+                self.update_aggregates(&boid);
+                Ok(())
+            }
+            Contents::Bucket(bucket) => {
+                bucket.push(boid.clone());
+                self.update_aggregates(&boid);
                 Ok(())
             }
             Contents::Children(children) => {
-                for child in children.iter_mut() {
-                    if child.insert(boid.clone()).is_err() {
-                        continue;
+                Self::insert_into_children(children, &boid, level + 1)?;
+                self.update_aggregates(&boid);
+                Ok(())
+            }
+        }
+    }
+
+    /// Empty children for the four quadrants of `boundary`, in the order of the two bits each
+    /// level adds to a Morton key: x, then y.
+    fn split(boundary: Boundary) -> [Child<T>; 4] {
+        let per_new_part = (boundary.max - boundary.min) / 2.0;
+        let boundary_min = boundary.min;
+
+        std::array::from_fn(|i| {
+            let (x, y) = match i {
+                0 => Some((0, 0)),
+                1 => Some((0, 1)),
+                2 => Some((1, 0)),
+                3 => Some((1, 1)),
+                _ => None,
+            }
+            .expect("Somehow the integer has exceeded 3");
+            let offset = Vector2::new(f64::from(x) * per_new_part.x, f64::from(y) * per_new_part.y);
+            let min = boundary_min + offset;
+            let max = min + per_new_part;
+            Box::new(Self::new(Boundary { min, max }))
+        })
+    }
+
+    /// Fills an empty node at `level` below the root with `bodies`, sorted by their Morton `keys`,
+    /// so that each child's bodies are a contiguous run of them. Nodes with at least
+    /// `parallel_bodies` bodies near the root build their children on a thread each.
+    fn build_sorted(&mut self, bodies: &[T], keys: &[u64], level: u32, parallel_bodies: usize) {
+        match bodies {
+            [] => {}
+            [body] => {
+                self.update_aggregates(body);
+                self.contents = Contents::Body(body.clone());
+            }
+            // Keys cannot tell these apart, so they share the leaf.
+            _ if level == morton::BITS => {
+                for body in bodies {
+                    self.update_aggregates(body);
+                }
+                self.contents = Contents::Bucket(bodies.to_vec());
+            }
+            _ => {
+                let shift = 2 * (morton::BITS - 1 - level);
+                let mut children = Self::split(self.boundary);
+                let mut ranges = Vec::with_capacity(4);
+                let mut start = 0;
+                for quadrant in 0..4 {
+                    let end = start
+                        + keys[start..].partition_point(|key| (key >> shift) & 0b11 <= quadrant);
+                    ranges.push(start..end);
+                    start = end;
+                }
+                let build = |child: &mut Child<T>, range: std::ops::Range<usize>| {
+                    child.build_sorted(
+                        &bodies[range.clone()],
+                        &keys[range],
+                        level + 1,
+                        parallel_bodies,
+                    );
+                };
+                // Subtrees share nothing, so the top few levels of large trees are built on a
+                // thread each.
+                if level < PARALLEL_LEVELS && bodies.len() >= parallel_bodies {
+                    thread::scope(|scope| {
+                        for (child, range) in children.iter_mut().zip(ranges) {
+                            scope.spawn(move || build(child, range));
+                        }
+                    });
+                } else {
+                    for (child, range) in children.iter_mut().zip(ranges) {
+                        build(child, range);
                     }
                 }
-                self.update_com(&boid);
-                Ok(())
+                for child in &children {
+                    self.absorb(child);
+                }
+                self.contents = Contents::Children(children);
             }
         }
     }
 
-    fn update_com(&mut self, boid: &Boid) {
+    /// Inserts `boid` into the first child that contains it. Children share their edges, so a body
+    /// on an edge must only go into one of them.
+    fn insert_into_children(children: &mut [Child<T>; 4], boid: &T, level: u32) -> InsertionResult {
+        let mut result = Ok(());
+        for child in children.iter_mut() {
+            result = child.insert(boid.clone(), level);
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+
+    fn update_aggregates(&mut self, boid: &T) {
         let new_mass = self.mass + boid.mass();
         let new_com =
             (self.center_of_mass * self.mass + boid.center_of_mass() * boid.mass()) / new_mass;
         self.mass = new_mass;
         self.center_of_mass = new_com;
+
+        let offset = boid.position() - self.boundary.center();
+        let mass = boid.mass();
+        self.second_moment[0] += mass * offset.x * offset.x;
+        self.second_moment[1] += mass * offset.x * offset.y;
+        self.second_moment[2] += mass * offset.y * offset.y;
+        self.bodies += 1;
+
+        self.charge.add(boid.charge(), boid.position());
+        self.circulation.add(boid.circulation(), boid.position());
     }
 
-    pub fn calculate_force(&self, body: &BoidRCell, theta: f64) -> Vector2<f64> {
-        if let Contents::Empty = self.contents {
-            return Vector2::default();
+    /// Replaces every body with `current(body)`, then takes out each one that has moved out of its
+    /// leaf, leaving the leaf empty.
+    fn remove_escaped(&mut self, current: &impl Fn(&T) -> T, escaped: &mut Vec<T>) {
+        match &mut self.contents {
+            Contents::Empty => {}
+            Contents::Body(body) => {
+                *body = current(body);
+                if !self.boundary.intersects(&body.position()) {
+                    escaped.push(body.clone());
+                    self.contents = Contents::Empty;
+                }
+            }
+            Contents::Bucket(bucket) => {
+                for body in bucket.iter_mut() {
+                    *body = current(body);
+                }
+                let boundary = self.boundary;
+                let (staying, leaving) = std::mem::take(bucket)
+                    .into_iter()
+                    .partition(|body| boundary.intersects(&body.position()));
+                *bucket = staying;
+                escaped.extend(leaving);
+            }
+            Contents::Children(children) => {
+                for child in children {
+                    child.remove_escaped(current, escaped);
+                }
+            }
+        }
+    }
+
+    /// Recomputes every aggregate from the bodies' current positions, bottom up, and collapses
+    /// nodes left with fewer than two bodies so that the tree is the same as one built afresh.
+    fn refresh(&mut self) {
+        let boundary = self.boundary;
+        let contents = std::mem::replace(&mut self.contents, Contents::Empty);
+        *self = Self::new(boundary);
+        match contents {
+            Contents::Empty => {}
+            Contents::Body(body) => {
+                self.update_aggregates(&body);
+                self.contents = Contents::Body(body);
+            }
+            Contents::Bucket(mut bucket) => {
+                for body in &bucket {
+                    self.update_aggregates(body);
+                }
+                self.contents = match bucket.len() {
+                    0 => Contents::Empty,
+                    1 => Contents::Body(bucket.remove(0)),
+                    _ => Contents::Bucket(bucket),
+                };
+            }
+            Contents::Children(mut children) => {
+                for child in &mut children {
+                    child.refresh();
+                }
+                match children.iter().map(|child| child.bodies).sum::<usize>() {
+                    0 => {}
+                    1 => {
+                        let body = children
+                            .iter()
+                            .find_map(|child| child.body())
+                            .expect("A node with one body has a leaf holding it")
+                            .clone();
+                        self.update_aggregates(&body);
+                        self.contents = Contents::Body(body);
+                    }
+                    _ => {
+                        for child in &children {
+                            self.absorb(child);
+                        }
+                        self.contents = Contents::Children(children);
+                    }
+                }
+            }
         }
+    }
+
+    /// Adds the aggregates of `child` to this node's.
+    fn absorb(&mut self, child: &Self) {
+        if child.mass != 0.0 {
+            let new_mass = self.mass + child.mass;
+            self.center_of_mass =
+                (self.center_of_mass * self.mass + child.center_of_mass * child.mass) / new_mass;
+            self.mass = new_mass;
+        }
+
+        // Move the child's second moments from its centre to ours.
+        let shift = child.boundary.center() - self.boundary.center();
+        let spread = (child.center_of_mass - child.boundary.center()) * child.mass;
+        let [xx, xy, yy] = child.second_moment;
+        self.second_moment[0] += xx + 2.0 * shift.x * spread.x + child.mass * shift.x * shift.x;
+        self.second_moment[1] +=
+            xy + shift.x * spread.y + shift.y * spread.x + child.mass * shift.x * shift.y;
+        self.second_moment[2] += yy + 2.0 * shift.y * spread.y + child.mass * shift.y * shift.y;
+        self.bodies += child.bodies;
+
+        for monopole in child.charge.iter() {
+            self.charge.add(monopole.strength, monopole.center);
+        }
+        for monopole in child.circulation.iter() {
+            self.circulation.add(monopole.strength, monopole.center);
+        }
+    }
+
+    /// Second moments of mass about the centre of mass, as `[xx, xy, yy]`.
+    fn quadrupole_moment(&self) -> [f64; 3] {
+        let [xx, xy, yy] = self.second_moment;
+        let center = self.center_of_mass - self.boundary.center();
+        [
+            xx - self.mass * center.x * center.x,
+            xy - self.mass * center.x * center.y,
+            yy - self.mass * center.y * center.y,
+        ]
+    }
+
+    /// Distance from the centre of mass to the farthest corner, which bounds how far any body in
+    /// the node lies from it.
+    fn b_max(&self) -> f64 {
+        let corners = [
+            self.boundary.min,
+            self.boundary.max,
+            Vector2::new(self.boundary.min.x, self.boundary.max.y),
+            Vector2::new(self.boundary.max.x, self.boundary.min.y),
+        ];
+        corners
+            .iter()
+            .map(|&corner| (corner - self.center_of_mass).magnitude())
+            .fold(0.0, f64::max)
+    }
+
+    /// Whether the node's expansion is accurate enough for bodies anywhere in `region`, whose
+    /// accelerations on the previous step were at least `acceleration`.
+    fn accepts<L: ForceLaw>(
+        &self,
+        region: Boundary,
+        acceleration: f64,
+        criterion: OpeningCriterion,
+        law: &L,
+    ) -> bool {
+        let Contents::Children(_) = self.contents else {
+            // Bodies in a leaf are taken one by one, which is exact at any distance.
+            return true;
+        };
+        // The centre of mass can lie close to or even past a body inside the node, where every
+        // criterion misjudges the error, so those nodes are always opened.
+        if self.boundary.intersects(&region) {
+            return false;
+        }
+        let width = self.boundary.half_size() * 2.0;
+        let distance = region.distance_to(self.center_of_mass);
+        match criterion {
+            OpeningCriterion::Geometric { theta } => width < theta * distance,
+            OpeningCriterion::SalmonWarren { theta } => self.b_max() < theta * distance,
+            OpeningCriterion::Relative { theta, .. } if acceleration == 0.0 => {
+                width < theta * distance
+            }
+            OpeningCriterion::Relative { tolerance, .. } => {
+                let error =
+                    law.magnitude(distance, 1.0, self.mass).abs() * (width / distance).powi(2);
+                error < tolerance * acceleration
+            }
+        }
+    }
 
-        let d = self.boundary.half_size() * 2.0;
-        let r = (body.position() - self.center_of_mass).magnitude();
+    /// Force on `body` from every body in the node, ignoring nodes entirely beyond `reach`, and an
+    /// estimate of its error from the expansions used along the way.
+    pub fn calculate_force<B: Item, L: ForceLaw>(
+        &self,
+        body: &B,
+        criterion: OpeningCriterion,
+        law: &L,
+        order: ExpansionOrder,
+        reach: f64,
+    ) -> (Vector2<f64>, f64) {
+        if let Contents::Empty = self.contents {
+            return (Vector2::default(), 0.0);
+        }
+        if self.boundary.distance_to(body.position()) > reach {
+            return (Vector2::default(), 0.0);
+        }
 
-        if d / r < theta {
-            if r == 0.0 {
-                return Vector2::default();
+        let acceleration = body.acceleration().magnitude();
+        let region = Boundary::from_center(body.position(), 0.0);
+        if self.accepts(region, acceleration, criterion, law) {
+            if let Contents::Body(_) | Contents::Bucket(_) = &self.contents {
+                // Bodies in a leaf have no spread to correct for. Their own positions are exact,
+                // where the centre of mass worked out from them can be an ulp away, which would
+                // leave a body pulling on itself from no distance at all.
+                let force = self
+                    .leaves()
+                    .iter()
+                    .fold(Vector2::default(), |force, leaf| {
+                        let displacement = leaf.position() - body.position();
+                        force + law.force(displacement, body.mass(), leaf.mass())
+                    });
+                return (force, 0.0);
+            }
+            let displacement = self.center_of_mass - body.position();
+            let monopole = law.force(displacement, body.mass(), self.mass);
+            // Every body lies within `b_max` of the centre of mass, so each term of the expansion
+            // is smaller than the one before by about `b_max / r`. The quadrupole term is the
+            // first one left out of a monopole, and the remaining ones sum to at most a geometric
+            // series after it.
+            let ratio = self.b_max() / displacement.magnitude();
+            let quadrupole =
+                law.quadrupole_force(displacement, body.mass(), self.quadrupole_moment());
+            if ratio >= 1.0 {
+                // The expansion need not converge at all, so it could be off by as much as it says.
+                let force = match order {
+                    ExpansionOrder::Monopole => monopole,
+                    ExpansionOrder::Quadrupole => monopole + quadrupole,
+                };
+                return (force, monopole.magnitude());
+            }
+            let tail = 1.0 / (1.0 - ratio);
+            return match order {
+                ExpansionOrder::Monopole => (monopole, quadrupole.magnitude() * tail),
+                ExpansionOrder::Quadrupole => {
+                    (monopole + quadrupole, quadrupole.magnitude() * ratio * tail)
+                }
+            };
+        }
+        match &self.contents {
+            Contents::Children(children) => {
+                let mut force = Vector2::default();
+                let mut error = 0.0;
+                for child in children {
+                    let (child_force, child_error) =
+                        child.calculate_force(body, criterion, law, order, reach);
+                    force = force + child_force;
+                    error += child_error;
+                }
+                (force, error)
             }
-            let direction = self.center_of_mass - body.position();
-            let force_magnitude = (GRAVITY * self.mass * body.mass()) / (r * r);
-            return direction * force_magnitude / r;
+            _ => (Vector2::default(), 0.0),
         }
+    }
+
+    /// Splits the node into groups of at most `group_size` bodies, taking each node as large as
+    /// possible.
+    fn groups<'a>(&'a self, group_size: usize, groups: &mut Vec<&'a Self>) {
         match &self.contents {
-            Contents::Boid(body2) => {
-                let direction = body2.position() - body.position();
-                let distance = direction.magnitude();
-                if distance == 0.0 {
-                    return Vector2::default();
+            Contents::Empty => {}
+            Contents::Children(children) if self.bodies > group_size => {
+                for child in children {
+                    child.groups(group_size, groups);
                 }
-                let force_magnitude = (GRAVITY * self.mass * body.mass()) / (distance * distance);
-                direction * force_magnitude / distance
             }
+            _ => groups.push(self),
+        }
+    }
+
+    /// Bodies in the node.
+    fn collect_bodies(&self, found: &mut Vec<T>) {
+        match &self.contents {
+            Contents::Empty => {}
+            Contents::Body(_) | Contents::Bucket(_) => found.extend_from_slice(self.leaves()),
             Contents::Children(children) => {
-                let mut force = Vector2::default();
                 for child in children {
-                    force = force + child.calculate_force(body, theta);
+                    child.collect_bodies(found);
+                }
+            }
+        }
+    }
+
+    /// Adds every source that a group of bodies in `region` sees from this node, opening nodes
+    /// until they are acceptable for the whole group at once.
+    fn interaction_list<L: ForceLaw>(
+        &self,
+        region: Boundary,
+        acceleration: f64,
+        criterion: OpeningCriterion,
+        law: &L,
+        order: ExpansionOrder,
+        list: &mut Vec<Interaction>,
+    ) {
+        match &self.contents {
+            Contents::Empty => {}
+            Contents::Children(children) if !self.accepts(region, acceleration, criterion, law) => {
+                for child in children {
+                    child.interaction_list(region, acceleration, criterion, law, order, list);
                 }
-                force
             }
+            // Bodies in a leaf go in at their own positions, as in `calculate_force`, so that they
+            // exert nothing on themselves.
+            Contents::Body(_) | Contents::Bucket(_) => {
+                list.extend(self.leaves().iter().map(|body| Interaction {
+                    position: body.position(),
+                    mass: body.mass(),
+                    moment: None,
+                }));
+            }
+            Contents::Children(_) => list.push(Interaction {
+                position: self.center_of_mass,
+                mass: self.mass,
+                moment: match order {
+                    ExpansionOrder::Quadrupole => Some(self.quadrupole_moment()),
+                    ExpansionOrder::Monopole => None,
+                },
+            }),
+        }
+    }
+
+    /// Electrostatic force on `body`, where `law` is applied to charges rather than masses.
+    pub fn calculate_electric_force<B: Item, L: ForceLaw>(
+        &self,
+        body: &B,
+        theta: f64,
+        law: &L,
+    ) -> Vector2<f64> {
+        let charge = body.charge();
+        self.sum_signed(
+            body.position(),
+            theta,
+            |node| node.charge,
+            T::charge,
+            &|displacement, source_charge| law.force(displacement, charge, source_charge),
+        )
+    }
+
+    /// Velocity induced at `body` by every point vortex in the node.
+    pub fn calculate_induced_velocity<B: Item>(
+        &self,
+        body: &B,
+        theta: f64,
+        kernel: PointVortices,
+    ) -> Vector2<f64> {
+        self.sum_signed(
+            body.position(),
+            theta,
+            |node| node.circulation,
+            T::circulation,
+            &|displacement, circulation| kernel.induced_velocity(displacement, circulation),
+        )
+    }
+
+    /// Sums `interaction` over the sources of a signed quantity, given the displacement from
+    /// `position` to each source and its strength. Both signs of a node are used only once both
+    /// are far enough away.
+    fn sum_signed(
+        &self,
+        position: Vector2<f64>,
+        theta: f64,
+        monopoles: fn(&Self) -> SignedMonopoles,
+        strength: fn(&T) -> f64,
+        interaction: &dyn Fn(Vector2<f64>, f64) -> Vector2<f64>,
+    ) -> Vector2<f64> {
+        match &self.contents {
             Contents::Empty => Vector2::default(),
+            Contents::Body(_) | Contents::Bucket(_) => self
+                .leaves()
+                .iter()
+                .fold(Vector2::default(), |total, body| {
+                    total + interaction(body.position() - position, strength(body))
+                }),
+            Contents::Children(children) => {
+                let d = self.boundary.half_size() * 2.0;
+                let sources = monopoles(self);
+                // A position inside the node would feel its own body through the monopoles, so
+                // such nodes are always opened, as in `accepts`.
+                let far = !self.boundary.intersects(&position)
+                    && sources
+                        .iter()
+                        .all(|monopole| d / (position - monopole.center).magnitude() < theta);
+                if far {
+                    sources.iter().fold(Vector2::default(), |total, monopole| {
+                        total + interaction(monopole.center - position, monopole.strength)
+                    })
+                } else {
+                    let mut total = Vector2::default();
+                    for child in children {
+                        total = total
+                            + child.sum_signed(position, theta, monopoles, strength, interaction);
+                    }
+                    total
+                }
+            }
+        }
+    }
+
+    /// Collects every body inside `rect` into `found`.
+    fn query_rect(&self, rect: Boundary, found: &mut Vec<T>) {
+        if !self.boundary.intersects(&rect) {
+            return;
+        }
+        match &self.contents {
+            Contents::Empty => {}
+            Contents::Body(_) | Contents::Bucket(_) => found.extend(
+                self.leaves()
+                    .iter()
+                    .filter(|boid| rect.intersects(&boid.position()))
+                    .cloned(),
+            ),
+            Contents::Children(children) => {
+                for child in children {
+                    child.query_rect(rect, found);
+                }
+            }
+        }
+    }
+
+    /// Collects every body within `radius` of `center` into `found`.
+    fn query_radius(&self, center: Vector2<f64>, radius: f64, found: &mut Vec<T>) {
+        if !self
+            .boundary
+            .intersects(&Boundary::from_center(center, radius))
+        {
+            return;
+        }
+        match &self.contents {
+            Contents::Empty => {}
+            Contents::Body(_) | Contents::Bucket(_) => found.extend(
+                self.leaves()
+                    .iter()
+                    .filter(|boid| (boid.position() - center).magnitude() <= radius)
+                    .cloned(),
+            ),
+            Contents::Children(children) => {
+                for child in children {
+                    child.query_radius(center, radius, found);
+                }
+            }
+        }
+    }
+
+    /// Offers every body that could be among the nearest to `point` to `nearest`, looking in the
+    /// closest children first.
+    fn k_nearest(&self, point: Vector2<f64>, nearest: &mut Nearest<T>) {
+        if self.boundary.distance_to(point) >= nearest.reach() {
+            return;
+        }
+        match &self.contents {
+            Contents::Empty => {}
+            Contents::Body(_) | Contents::Bucket(_) => {
+                for body in self.leaves() {
+                    nearest.offer((body.position() - point).magnitude(), body);
+                }
+            }
+            Contents::Children(children) => {
+                let mut order: Vec<_> = children.iter().collect();
+                order.sort_by(|a, b| {
+                    a.boundary
+                        .distance_to(point)
+                        .total_cmp(&b.boundary.distance_to(point))
+                });
+                for child in order {
+                    child.k_nearest(point, nearest);
+                }
+            }
+        }
+    }
+
+    fn visit_mass(&self, open: &mut dyn FnMut(&MassGroup<T>) -> bool) {
+        let group = MassGroup {
+            mass: self.mass,
+            center_of_mass: self.center_of_mass,
+            bounds: self.boundary,
+            body: self.body(),
+        };
+        match &self.contents {
+            Contents::Empty => {}
+            Contents::Body(_) => {
+                open(&group);
+            }
+            Contents::Bucket(bucket) => {
+                if open(&group) {
+                    for body in bucket {
+                        open(&MassGroup {
+                            mass: body.mass(),
+                            center_of_mass: body.position(),
+                            bounds: Boundary::from_center(body.position(), 0.0),
+                            body: Some(body),
+                        });
+                    }
+                }
+            }
+            Contents::Children(children) => {
+                if open(&group) {
+                    for child in children {
+                        child.visit_mass(open);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    /// The body held by a leaf.
+    pub fn body(&self) -> Option<&T> {
+        match &self.contents {
+            Contents::Body(body) => Some(body),
+            _ => None,
+        }
+    }
+
+    /// Every body held by a leaf, or none for a node that has been split.
+    pub fn leaves(&self) -> &[T] {
+        match &self.contents {
+            Contents::Body(body) => std::slice::from_ref(body),
+            Contents::Bucket(bucket) => bucket,
+            Contents::Empty | Contents::Children(_) => &[],
         }
     }
 
+    /// The children of a node that has been split, skipping empty ones.
+    pub fn children(&self) -> impl Iterator<Item = &Self> {
+        let children = match &self.contents {
+            Contents::Children(children) => &children[..],
+            _ => &[],
+        };
+        children
+            .iter()
+            .map(|child| &**child)
+            .filter(|child| !matches!(child.contents, Contents::Empty))
+    }
+
     pub fn boundaries(&self) -> Vec<Boundary> {
         if let Contents::Children(children) = &self.contents {
             children.iter().flat_map(|c| c.boundaries()).collect()
@@ -153,13 +891,13 @@ impl Node {
         }
     }
 }
-#[derive(Debug)]
-pub struct Quadtree {
-    head: Node,
-    boids: Vec<BoidRCell>,
+#[derive(Debug, Clone)]
+pub struct Quadtree<T = Particle> {
+    head: Node<T>,
+    boids: Vec<T>,
 }
 
-impl Quadtree {
+impl<T: Item> Quadtree<T> {
     pub fn new(boundary: Boundary) -> Self {
         Self {
             head: Node::new(boundary),
@@ -167,14 +905,149 @@ impl Quadtree {
         }
     }
 
-    pub fn insert(&mut self, boid: BoidRCell) -> InsertionResult {
-        self.head.insert(boid.clone())?;
-        self.boids.push(boid);
-        Ok(())
+    /// Builds the tree bottom up from `bodies` sorted with [`morton::sort`] over `boundary`, which
+    /// needs no search from the root for each body. Bodies outside `boundary` are left out.
+    pub fn from_sorted(boundary: Boundary, bodies: &[T]) -> Self {
+        Self::from_sorted_in_parallel_above(boundary, bodies, PARALLEL_BODIES)
+    }
+
+    /// [`Self::from_sorted`], spreading nodes of at least `parallel_bodies` bodies over threads.
+    fn from_sorted_in_parallel_above(
+        boundary: Boundary,
+        bodies: &[T],
+        parallel_bodies: usize,
+    ) -> Self {
+        let boids: Vec<_> = bodies
+            .iter()
+            .filter(|body| boundary.intersects(&body.position()))
+            .cloned()
+            .collect();
+        let keys: Vec<_> = boids
+            .iter()
+            .map(|body| morton::key(body.position(), boundary))
+            .collect();
+        let mut tree = Self::new(boundary);
+        tree.head.build_sorted(&boids, &keys, 0, parallel_bodies);
+        tree.boids = boids;
+        tree
+    }
+
+    /// Brings the tree up to date after its bodies have moved, replacing each with `current(body)`,
+    /// moving only those that have left their leaf and recomputing the aggregates bottom up.
+    /// Returns `false` when it needs rebuilding instead: when a body has left the tree altogether,
+    /// or when the bodies have drawn into less than half its width, which would leave it
+    /// needlessly deep.
+    pub fn update(&mut self, current: impl Fn(&T) -> T) -> bool {
+        for body in &mut self.boids {
+            *body = current(body);
+        }
+        let Some(first) = self.boids.first() else {
+            return true;
+        };
+        let (mut min, mut max) = (first.position(), first.position());
+        for body in &self.boids {
+            let position = body.position();
+            if !self.head.boundary.intersects(&position) {
+                return false;
+            }
+            min = Vector2::new(min.x.min(position.x), min.y.min(position.y));
+            max = Vector2::new(max.x.max(position.x), max.y.max(position.y));
+        }
+        let extent = (max.x - min.x).max(max.y - min.y);
+        if extent < self.head.boundary.half_size() {
+            return false;
+        }
+
+        let mut escaped = Vec::new();
+        self.head.remove_escaped(&current, &mut escaped);
+        for body in escaped {
+            if self.head.insert(body, 0).is_err() {
+                return false;
+            }
+        }
+        self.head.refresh();
+        true
+    }
+
+    pub fn calculate_force<B: Item, L: ForceLaw>(
+        &self,
+        body: &B,
+        criterion: OpeningCriterion,
+        law: &L,
+        order: ExpansionOrder,
+    ) -> Vector2<f64> {
+        self.head
+            .calculate_force(body, criterion, law, order, f64::INFINITY)
+            .0
+    }
+
+    /// Force on `body` together with an estimate of its error.
+    pub fn estimate_force<B: Item, L: ForceLaw>(
+        &self,
+        body: &B,
+        criterion: OpeningCriterion,
+        law: &L,
+        order: ExpansionOrder,
+    ) -> (Vector2<f64>, f64) {
+        self.head
+            .calculate_force(body, criterion, law, order, f64::INFINITY)
+    }
+
+    /// Force on `body` from bodies within `reach`, for laws that vanish beyond it.
+    pub fn calculate_short_range_force<B: Item, L: ForceLaw>(
+        &self,
+        body: &B,
+        criterion: OpeningCriterion,
+        law: &L,
+        order: ExpansionOrder,
+        reach: f64,
+    ) -> Vector2<f64> {
+        self.head
+            .calculate_force(body, criterion, law, order, reach)
+            .0
+    }
+
+    pub fn calculate_electric_force<B: Item, L: ForceLaw>(
+        &self,
+        body: &B,
+        theta: f64,
+        law: &L,
+    ) -> Vector2<f64> {
+        self.head.calculate_electric_force(body, theta, law)
+    }
+
+    /// Every body inside `rect`, edges included.
+    pub fn query_rect(&self, rect: Boundary) -> Vec<T> {
+        let mut found = Vec::new();
+        self.head.query_rect(rect, &mut found);
+        found
+    }
+
+    /// Every body within `radius` of `center`.
+    pub fn query_radius(&self, center: Vector2<f64>, radius: f64) -> Vec<T> {
+        let mut found = Vec::new();
+        self.head.query_radius(center, radius, &mut found);
+        found
+    }
+
+    /// The `k` bodies nearest to `point`, nearest first.
+    pub fn k_nearest(&self, point: Vector2<f64>, k: usize) -> Vec<T> {
+        let mut nearest = Nearest::new(k);
+        self.head.k_nearest(point, &mut nearest);
+        nearest.into_bodies()
     }
 
-    pub fn calculate_force(&self, body: &BoidRCell, theta: f64) -> Vector2<f64> {
-        self.head.calculate_force(body, theta)
+    pub fn calculate_induced_velocity<B: Item>(
+        &self,
+        body: &B,
+        theta: f64,
+        kernel: PointVortices,
+    ) -> Vector2<f64> {
+        self.head.calculate_induced_velocity(body, theta, kernel)
+    }
+
+    pub fn root(&self) -> &Node<T> {
+        &self.head
     }
 
     pub fn boundaries(&self) -> Vec<Boundary> {
@@ -187,3 +1060,337 @@ impl Quadtree {
         self.head.boundary
     }
 }
+
+impl Quadtree {
+    /// Gravity on every body, walking the tree once for each group of up to `group_size` nearby
+    /// bodies rather than once for each body. Every body in a group shares one interaction list,
+    /// built with the criterion applied to the whole group, so forces are at least as accurate as
+    /// from [`Self::calculate_force`].
+    pub fn calculate_group_forces<L: ForceLaw>(
+        &self,
+        criterion: OpeningCriterion,
+        law: &L,
+        order: ExpansionOrder,
+        group_size: usize,
+    ) -> GroupForces {
+        let mut groups = Vec::new();
+        self.head.groups(group_size.max(1), &mut groups);
+
+        let mut forces = HashMap::with_capacity(self.boids.len());
+        let mut list = Vec::new();
+        let mut bodies = Vec::new();
+        for group in groups {
+            bodies.clear();
+            group.collect_bodies(&mut bodies);
+            let positions: Vec<_> = bodies.iter().map(Position::position).collect();
+            let min = positions.iter().fold(positions[0], |min, position| {
+                Vector2::new(min.x.min(position.x), min.y.min(position.y))
+            });
+            let max = positions.iter().fold(positions[0], |max, position| {
+                Vector2::new(max.x.max(position.x), max.y.max(position.y))
+            });
+            let acceleration = bodies
+                .iter()
+                .map(|body| body.acceleration().magnitude())
+                .fold(f64::INFINITY, f64::min);
+
+            list.clear();
+            self.head.interaction_list(
+                Boundary { min, max },
+                acceleration,
+                criterion,
+                law,
+                order,
+                &mut list,
+            );
+            for (body, position) in bodies.iter().zip(positions) {
+                let mass = body.mass();
+                let force = list.iter().fold(Vector2::default(), |force, source| {
+                    let displacement = source.position - position;
+                    if displacement == Vector2::default() {
+                        // The body itself.
+                        return force;
+                    }
+                    let monopole = law.force(displacement, mass, source.mass);
+                    force
+                        + source.moment.map_or(monopole, |moment| {
+                            monopole + law.quadrupole_force(displacement, mass, moment)
+                        })
+                });
+                forces.insert(body.index, force);
+            }
+        }
+        GroupForces(forces)
+    }
+}
+
+impl SpatialIndex for Quadtree {
+    fn build(bodies: &Bodies) -> Self {
+        let mut sorted = bodies.particles();
+        let fitted = spatial::bounding_box(&sorted);
+        let square = Boundary::new(fitted.min, fitted.max);
+        // Leave a sliver of room so that rounding in the square cannot shut out the outermost
+        // bodies.
+        let bounds = Boundary::from_center(square.center(), square.half_size() * (1.0 + 1e-9));
+        morton::sort(&mut sorted, bounds);
+        Self::from_sorted(bounds, &sorted)
+    }
+
+    fn query_rect(&self, rect: Boundary) -> Vec<Particle> {
+        Self::query_rect(self, rect)
+    }
+
+    fn query_radius(&self, center: Vector2<f64>, radius: f64) -> Vec<Particle> {
+        Self::query_radius(self, center, radius)
+    }
+
+    fn k_nearest(&self, point: Vector2<f64>, k: usize) -> Vec<Particle> {
+        Self::k_nearest(self, point, k)
+    }
+
+    fn visit_mass(&self, open: &mut dyn FnMut(&MassGroup) -> bool) {
+        self.head.visit_mass(open);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        boid::Boid,
+        direct,
+        force_law::{Coulomb, Newtonian},
+        random::Random,
+    };
+
+    /// Bodies scattered at random over a square, with masses between 1 and 10.
+    fn scattered(count: usize, seed: u64) -> Bodies {
+        let mut random = Random::new(seed);
+        (0..count)
+            .map(|_| {
+                Boid::new(
+                    random.uniform() * 1000.0,
+                    random.uniform() * 1000.0,
+                    1.0 + random.uniform() * 9.0,
+                )
+            })
+            .collect()
+    }
+
+    /// The same bodies, each with a charge of one or minus one.
+    fn charged(count: usize, seed: u64) -> Bodies {
+        let mut random = Random::new(seed);
+        scattered(count, seed)
+            .iter()
+            .map(|body| {
+                let mut boid = Boid::new(body.position().x, body.position().y, body.mass());
+                boid.set_charge(if random.uniform() < 0.5 { -1.0 } else { 1.0 });
+                boid
+            })
+            .collect()
+    }
+
+    /// A lone body in one corner, with a cluster in the far corner whose signed monopoles pass the
+    /// opening test from there for the whole tree, the lone body's own included. `set` gives each
+    /// body its strength of one or minus one.
+    fn lone_in_a_corner(set: fn(&mut Boid, f64)) -> Bodies {
+        let mut random = Random::new(17);
+        let mut lone = Boid::new(0.0, 0.0, 1.0);
+        set(&mut lone, 1.0);
+        std::iter::once(lone)
+            .chain((0..40).map(|i| {
+                let mut boid = Boid::new(9.0 + random.uniform(), 9.0 + random.uniform(), 1.0);
+                set(&mut boid, if i % 4 == 0 { 1.0 } else { -1.0 });
+                boid
+            }))
+            .collect()
+    }
+
+    /// Each body's relative error in `approximate` from `exact`, smallest first.
+    fn relative_errors(
+        bodies: &Bodies,
+        exact: impl Fn(BodyRef) -> Vector2<f64>,
+        approximate: impl Fn(BodyRef) -> Vector2<f64>,
+    ) -> Vec<f64> {
+        let mut errors: Vec<_> = bodies
+            .iter()
+            .map(|body| {
+                let exact = exact(body);
+                (approximate(body) - exact).magnitude() / exact.magnitude()
+            })
+            .collect();
+        errors.sort_by(f64::total_cmp);
+        errors
+    }
+
+    #[test]
+    fn tree_matches_direct_summation() {
+        let bodies = scattered(500, 7);
+        let tree = Quadtree::build(&bodies);
+        let law = Newtonian { gravity: 1.0 };
+        let criterion = OpeningCriterion::Geometric { theta: 0.5 };
+        for (order, median) in [
+            (ExpansionOrder::Monopole, 0.05),
+            (ExpansionOrder::Quadrupole, 0.01),
+        ] {
+            let errors = relative_errors(
+                &bodies,
+                |body| direct::calculate_force(&bodies, body, &law),
+                |body| tree.calculate_force(&body, criterion, &law, order),
+            );
+            assert!(errors[errors.len() / 2] < median, "{order:?}: {errors:?}");
+            assert!(errors[errors.len() - 1] < 0.5, "{order:?}: {errors:?}");
+        }
+    }
+
+    #[test]
+    fn group_walk_matches_direct_summation() {
+        let bodies = scattered(500, 11);
+        let tree = Quadtree::build(&bodies);
+        let law = Newtonian { gravity: 1.0 };
+        let criterion = OpeningCriterion::Geometric { theta: 0.5 };
+        let forces = tree.calculate_group_forces(criterion, &law, ExpansionOrder::Quadrupole, 16);
+        let errors = relative_errors(
+            &bodies,
+            |body| direct::calculate_force(&bodies, body, &law),
+            |body| forces.get(body),
+        );
+        assert!(errors[errors.len() / 2] < 0.01, "{errors:?}");
+        assert!(errors[errors.len() - 1] < 0.5, "{errors:?}");
+    }
+
+    #[test]
+    fn electric_force_matches_direct_summation() {
+        let law = Coulomb {
+            constant: 1.0,
+            softening: 0.0,
+        };
+        let bodies = charged(500, 13);
+        let tree = Quadtree::build(&bodies);
+        let errors = relative_errors(
+            &bodies,
+            |body| direct::calculate_electric_force(&bodies, body, &law),
+            |body| tree.calculate_electric_force(&body, 0.9, &law),
+        );
+        assert!(errors[errors.len() / 2] < 0.05, "{errors:?}");
+        assert!(errors[errors.len() - 1] < 1.5, "{errors:?}");
+
+        let bodies = lone_in_a_corner(Boid::set_charge);
+        let tree = Quadtree::build(&bodies);
+        let exact = direct::calculate_electric_force(&bodies, bodies.get(0), &law);
+        let approximate = tree.calculate_electric_force(&bodies.get(0), 0.9, &law);
+        assert!(
+            (approximate - exact).magnitude() < 1e-2 * exact.magnitude(),
+            "{approximate:?} against {exact:?}"
+        );
+    }
+
+    #[test]
+    fn induced_velocity_matches_direct_summation() {
+        let kernel = PointVortices { softening: 0.0 };
+        let bodies: Bodies = charged(500, 13)
+            .iter()
+            .map(|body| {
+                let mut boid = Boid::new(body.position().x, body.position().y, body.mass());
+                boid.set_circulation(body.charge());
+                boid
+            })
+            .collect();
+        let tree = Quadtree::build(&bodies);
+        let errors = relative_errors(
+            &bodies,
+            |body| direct::calculate_induced_velocity(&bodies, body, kernel),
+            |body| tree.calculate_induced_velocity(&body, 0.5, kernel),
+        );
+        assert!(errors[errors.len() / 2] < 0.05, "{errors:?}");
+        assert!(errors[errors.len() - 1] < 1.0, "{errors:?}");
+
+        let bodies = lone_in_a_corner(Boid::set_circulation);
+        let tree = Quadtree::build(&bodies);
+        let exact = direct::calculate_induced_velocity(&bodies, bodies.get(0), kernel);
+        let approximate = tree.calculate_induced_velocity(&bodies.get(0), 0.9, kernel);
+        assert!(
+            (approximate - exact).magnitude() < 1e-2 * exact.magnitude(),
+            "{approximate:?} against {exact:?}"
+        );
+    }
+
+    /// Number of nodes in the subtrees under `a` and `b`, checking on the way down that they have
+    /// the same shape and the same monopoles.
+    #[allow(clippy::float_cmp)]
+    fn compare(a: &Node, b: &Node) -> usize {
+        assert_eq!(a.boundary, b.boundary);
+        assert_eq!(a.bodies, b.bodies);
+        assert_eq!(a.mass, b.mass);
+        assert_eq!(a.center_of_mass, b.center_of_mass);
+        assert_eq!(a.second_moment, b.second_moment);
+        for (a, b) in a.charge.iter().zip(b.charge.iter()) {
+            assert_eq!((a.strength, a.center), (b.strength, b.center));
+        }
+        assert_eq!(a.children().count(), b.children().count());
+        1 + a
+            .children()
+            .zip(b.children())
+            .map(|(a, b)| compare(a, b))
+            .sum::<usize>()
+    }
+
+    #[test]
+    fn parallel_build_matches_serial() {
+        let bodies = charged(2 * PARALLEL_BODIES, 23);
+        let parallel = Quadtree::build(&bodies);
+        let bounds = parallel.outer_bounds();
+        let mut sorted = bodies.particles();
+        morton::sort(&mut sorted, bounds);
+        let serial = Quadtree::from_sorted_in_parallel_above(bounds, &sorted, usize::MAX);
+        assert!(compare(parallel.root(), serial.root()) > PARALLEL_BODIES);
+
+        let criterion = OpeningCriterion::Geometric { theta: 0.5 };
+        let law = Newtonian { gravity: 1.0 };
+        let coulomb = Coulomb {
+            constant: 1.0,
+            softening: 0.0,
+        };
+        for body in bodies.iter() {
+            assert_eq!(
+                parallel.calculate_force(&body, criterion, &law, ExpansionOrder::Quadrupole),
+                serial.calculate_force(&body, criterion, &law, ExpansionOrder::Quadrupole)
+            );
+            assert_eq!(
+                parallel.calculate_electric_force(&body, 0.5, &coulomb),
+                serial.calculate_electric_force(&body, 0.5, &coulomb)
+            );
+        }
+    }
+
+    #[test]
+    fn coincident_bodies_share_a_leaf() {
+        let mut bodies = scattered(50, 5);
+        for _ in 0..5 {
+            bodies.push(Boid::new(500.0, 500.0, 1.0));
+        }
+        let mut tree = Quadtree::build(&bodies);
+        let law = Newtonian { gravity: 1.0 };
+        let criterion = OpeningCriterion::Geometric { theta: 0.5 };
+        let center = Vector2::new(500.0, 500.0);
+        assert_eq!(tree.query_radius(center, 0.0).len(), 5);
+        let errors = relative_errors(
+            &bodies,
+            |body| direct::calculate_force(&bodies, body, &law),
+            |body| tree.calculate_force(&body, criterion, &law, ExpansionOrder::Quadrupole),
+        );
+        assert!(errors[errors.len() - 1] < 0.5, "{errors:?}");
+
+        // Moving them together to another leaf inserts them there one by one.
+        let moved = Vector2::new(250.0, 750.0);
+        assert!(tree.update(|particle| {
+            let mut particle = *particle;
+            if particle.position == center {
+                particle.position = moved;
+            }
+            particle
+        }));
+        assert_eq!(tree.query_radius(moved, 0.0).len(), 5);
+        assert!(tree.query_radius(center, 0.0).is_empty());
+    }
+}
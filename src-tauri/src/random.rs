@@ -0,0 +1,49 @@
+//! A small seedable pseudo-random generator, good enough for thermal noise and initial conditions.
+
+use std::f64::consts::TAU;
+
+/// Marsaglia's xorshift64* generator.
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        // An all-zero state would only ever produce zeros.
+        Self { state: seed.max(1) }
+    }
+
+    /// Seeded from the clock, for noise that should differ between runs.
+    pub fn from_time() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos());
+        // Only the fast-changing low bits matter for a seed.
+        #[allow(clippy::cast_possible_truncation)]
+        Self::new(nanos as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn uniform(&mut self) -> f64 {
+        // The top 53 bits fill an f64 mantissa exactly.
+        #[allow(clippy::cast_precision_loss)]
+        let mantissa = (self.next_u64() >> 11) as f64;
+        // Divided by 2^53.
+        mantissa / 9_007_199_254_740_992.0
+    }
+
+    /// Standard normal, by the Box–Muller transform.
+    pub fn gaussian(&mut self) -> f64 {
+        // 1 - u is in (0, 1], so the logarithm stays finite.
+        let radius = (-2.0 * (1.0 - self.uniform()).ln()).sqrt();
+        radius * (TAU * self.uniform()).cos()
+    }
+}
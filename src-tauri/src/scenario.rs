@@ -0,0 +1,400 @@
+use std::f64::consts::PI;
+
+use crate::{
+    boid::Boid,
+    boundary::Boundary,
+    field::{ElectricField, MagneticField},
+    flocking,
+    flocking::Flocking,
+    force_law::{Coulomb, ExpansionOrder, ForceLawKind, Law, Softened},
+    integrator::Integrator,
+    molecular::{self, LennardJones, Thermostat},
+    orbit::OrbitalElements,
+    plasma,
+    quadtree::OpeningCriterion,
+    random::Random,
+    solar_system,
+    spatial::NeighbourIndex,
+    sph::{self, Sph},
+    three_body,
+    units::UnitSystem,
+    vector::Vector2,
+    vortex::{self, PointVortices},
+    DT, GRAVITY, THETA,
+};
+
+const MASS_ONE: f64 = 125e12;
+const MASS_TWO: f64 = 10e11;
+const SATELITE_MASS: f64 = 10e9;
+const CENTER_X: f64 = 250.0;
+const CENTER_Y: f64 = 250.0;
+
+/// How the force on each body is summed.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum Solver {
+    /// Approximate far away bodies by walking the quadtree.
+    BarnesHut,
+    /// Sum over every pair of bodies.
+    Direct,
+    /// Solve for gravity on a `cells` by `cells` grid. Other interactions still use the tree.
+    ParticleMesh { cells: usize },
+    /// Gravity from the grid beyond `split` grid cells and from the tree within it.
+    TreePm { cells: usize, split: f64 },
+    /// Fast multipole expansions to `order` terms, for the logarithmic force law and point
+    /// vortices, whose kernels are harmonic in two dimensions. Other force laws use the tree.
+    Fmm { order: usize },
+    /// Barnes–Hut over a k-d tree, which stays balanced when the bodies are strongly clustered.
+    KdTree,
+}
+
+/// Fewest cells across that the particle mesh can interpolate on.
+const MIN_MESH_CELLS: usize = 4;
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidSolver {
+    #[error("the mesh needs a power of two of at least {MIN_MESH_CELLS} cells across, not {0}")]
+    MeshCells(usize),
+}
+
+impl serde::Serialize for InvalidSolver {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Solver {
+    /// The solver, if it can run as set. The mesh's transforms need a power of two cells across.
+    pub fn validate(self) -> Result<Self, InvalidSolver> {
+        match self {
+            Self::ParticleMesh { cells } | Self::TreePm { cells, .. }
+                if cells < MIN_MESH_CELLS || !cells.is_power_of_two() =>
+            {
+                Err(InvalidSolver::MeshCells(cells))
+            }
+            _ => Ok(self),
+        }
+    }
+}
+
+/// Physical constants the simulation loop runs with.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct Parameters {
+    pub units: UnitSystem,
+    /// Simulated time advanced each tick, in `units.time`.
+    pub dt: f64,
+    pub force_law: ForceLawKind,
+    /// Plummer softening length for gravity, in `units.length`.
+    pub softening: f64,
+    /// Whether bodies attract each other under `force_law`.
+    pub self_gravity: bool,
+    /// Coulomb interaction between charged bodies, on top of gravity.
+    pub electrostatics: Option<Coulomb>,
+    pub solver: Solver,
+    /// Multipole order of far away tree nodes for gravity.
+    pub expansion: ExpansionOrder,
+    /// When the tree walk for gravity uses a node's expansion instead of opening it.
+    pub opening: OpeningCriterion,
+    /// Target relative error of each body's gravity from the tree. When set, every body adapts
+    /// its own opening angle to meet it, starting from that of `opening`.
+    pub accuracy: Option<f64>,
+    /// When set, the tree walk for gravity is shared by groups of up to this many nearby bodies,
+    /// which takes precedence over adapting the opening angle to `accuracy`.
+    pub group_size: Option<usize>,
+    /// External out-of-plane magnetic field acting on charged bodies.
+    pub magnetic_field: Option<MagneticField>,
+    /// External electric field acting on charged bodies.
+    pub electric_field: Option<ElectricField>,
+    pub integrator: Integrator,
+    /// Steering rules that make bodies flock, on top of any physical forces.
+    pub flocking: Option<Flocking>,
+    /// When set, bodies are point vortices carried along by each other's flow, and every force
+    /// above is ignored.
+    pub point_vortices: Option<PointVortices>,
+    /// Short-range Lennard-Jones interaction between every pair of bodies.
+    pub lennard_jones: Option<LennardJones>,
+    /// Box that bodies wrap around, leaving one side and coming back in the other.
+    pub periodic_cell: Option<Boundary>,
+    pub thermostat: Option<Thermostat>,
+    /// Hydrodynamics for the bodies that are gas particles.
+    pub sph: Option<Sph>,
+    /// Where Lennard-Jones, SPH and flocking look for neighbours.
+    pub neighbour_index: NeighbourIndex,
+}
+
+impl Parameters {
+    pub const fn new(units: UnitSystem, dt: f64) -> Self {
+        Self {
+            units,
+            dt,
+            force_law: ForceLawKind::Newtonian,
+            softening: 0.0,
+            self_gravity: true,
+            electrostatics: None,
+            solver: Solver::BarnesHut,
+            expansion: ExpansionOrder::Quadrupole,
+            opening: OpeningCriterion::Geometric { theta: THETA },
+            accuracy: None,
+            group_size: None,
+            magnetic_field: None,
+            electric_field: None,
+            integrator: Integrator::SemiImplicitEuler,
+            flocking: None,
+            point_vortices: None,
+            lennard_jones: None,
+            periodic_cell: None,
+            thermostat: None,
+            sph: None,
+            neighbour_index: NeighbourIndex::Grid,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_electrostatics(mut self, coulomb: Coulomb) -> Self {
+        self.electrostatics = Some(coulomb);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_solver(mut self, solver: Solver) -> Self {
+        self.solver = solver;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_magnetic_field(mut self, field: MagneticField) -> Self {
+        self.magnetic_field = Some(field);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_electric_field(mut self, field: ElectricField) -> Self {
+        self.electric_field = Some(field);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_integrator(mut self, integrator: Integrator) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_flocking(mut self, flocking: Flocking) -> Self {
+        self.flocking = Some(flocking);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_point_vortices(mut self, point_vortices: PointVortices) -> Self {
+        self.point_vortices = Some(point_vortices);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_lennard_jones(mut self, lennard_jones: LennardJones) -> Self {
+        self.lennard_jones = Some(lennard_jones);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_periodic_cell(mut self, cell: Boundary) -> Self {
+        self.periodic_cell = Some(cell);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_thermostat(mut self, thermostat: Thermostat) -> Self {
+        self.thermostat = Some(thermostat);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_sph(mut self, sph: Sph) -> Self {
+        self.sph = Some(sph);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_softening(mut self, softening: f64) -> Self {
+        self.softening = softening;
+        self
+    }
+
+    #[must_use]
+    pub const fn without_self_gravity(mut self) -> Self {
+        self.self_gravity = false;
+        self
+    }
+
+    /// Gravitational constant expressed in the scenario's units.
+    pub fn gravity(&self) -> f64 {
+        self.units.gravity()
+    }
+
+    pub fn force_law(&self) -> Softened<Law> {
+        Softened {
+            law: self.force_law.with_gravity(self.gravity()),
+            length: self.softening,
+        }
+    }
+}
+
+pub struct Scenario {
+    pub bodies: Vec<Boid>,
+    pub parameters: Parameters,
+    /// Time after which every body should return to its starting state, for scenarios with a
+    /// known periodic solution.
+    pub period: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Preset {
+    StableOrbits,
+    SolarSystem,
+    FigureEight,
+    Lagrange,
+    Euler,
+    Pythagorean,
+    BrouckeA1,
+    BrouckeA2,
+    BrouckeR1,
+    PlasmaBlob,
+    IonCrystal,
+    ExBDrift,
+    PenningTrap,
+    Flock,
+    VortexStreet,
+    KelvinHelmholtz,
+    LennardJonesFluid,
+    ProtostellarCollapse,
+    GasDisk,
+    UniformCollapse,
+}
+
+impl Preset {
+    pub fn scenario(self) -> Scenario {
+        match self {
+            Self::StableOrbits => Scenario {
+                bodies: stable_orbits(Vector2::new(CENTER_X, CENTER_Y)).into(),
+                parameters: Parameters::new(UnitSystem::SI, DT),
+                period: None,
+            },
+            Self::SolarSystem => solar_system::scenario(),
+            Self::FigureEight => three_body::figure_eight(),
+            Self::Lagrange => three_body::lagrange(),
+            Self::Euler => three_body::euler(),
+            Self::Pythagorean => three_body::pythagorean(),
+            Self::BrouckeA1 => three_body::broucke_a1(),
+            Self::BrouckeA2 => three_body::broucke_a2(),
+            Self::BrouckeR1 => three_body::broucke_r1(),
+            Self::PlasmaBlob => plasma::plasma_blob(10.0),
+            Self::IonCrystal => plasma::ion_crystal(3),
+            Self::ExBDrift => plasma::exb_drift(),
+            Self::PenningTrap => plasma::penning_trap(8),
+            Self::Flock => flocking::flock(200),
+            Self::VortexStreet => vortex::street(20),
+            Self::KelvinHelmholtz => vortex::shear_layer(200),
+            Self::LennardJonesFluid => molecular::lennard_jones_fluid(20, 0.7, 1.0),
+            Self::ProtostellarCollapse => sph::protostellar_collapse(400),
+            Self::GasDisk => sph::gas_disk(500),
+            Self::UniformCollapse => uniform_collapse(4000),
+        }
+    }
+}
+
+/// Places a body of `mass` on the orbit described by `elements` around `primary`.
+pub fn satellite(primary: &Boid, mass: f64, elements: OrbitalElements, gravity: f64) -> Boid {
+    let state = elements
+        .to_state_vector(gravity * (primary.mass() + mass))
+        .expect("preset orbits should be valid conic sections");
+    let position = primary.position() + state.position;
+    let mut boid = Boid::new(position.x, position.y, mass);
+    boid.set_velocity(primary.velocity() + state.velocity);
+    boid
+}
+
+/// `count` bodies of equal mass scattered uniformly over a square, starting at rest. There are too
+/// many for Barnes–Hut to keep up, so it runs with tree-PM.
+fn uniform_collapse(count: u32) -> Scenario {
+    const SIDE: f64 = 2.0;
+    let mass = 1.0 / f64::from(count);
+    let mut random = Random::new(u64::from(count));
+    let bodies = (0..count)
+        .map(|_| {
+            let position = Vector2::new(random.uniform() - 0.5, random.uniform() - 0.5) * SIDE;
+            Boid::new(position.x, position.y, mass)
+        })
+        .collect();
+    Scenario {
+        bodies,
+        parameters: Parameters::new(UnitSystem::NBODY, 1e-3)
+            .with_softening(0.01)
+            .with_solver(Solver::TreePm {
+                cells: 64,
+                split: 1.25,
+            }),
+        period: None,
+    }
+}
+
+fn stable_orbits(center: Vector2<f64>) -> [Boid; 6] {
+    let mass_one = {
+        let mut boid = Boid::new(center.x, center.y, MASS_ONE);
+        boid.set_velocity(Vector2::new(0.0, 0.0));
+        boid
+    };
+    let mass_two = satellite(
+        &mass_one,
+        MASS_TWO,
+        OrbitalElements::circular(100.0, 0.0),
+        GRAVITY,
+    );
+    let mass_three = satellite(
+        &mass_one,
+        MASS_TWO,
+        OrbitalElements::circular(100.0, PI),
+        GRAVITY,
+    );
+    let mass_four = satellite(
+        &mass_one,
+        SATELITE_MASS,
+        OrbitalElements::circular(100.0, PI / 3.0),
+        GRAVITY,
+    );
+    let mass_five = satellite(
+        &mass_one,
+        SATELITE_MASS,
+        OrbitalElements::circular(100.0, -PI / 3.0),
+        GRAVITY,
+    );
+    let mass_six = satellite(
+        &mass_two,
+        SATELITE_MASS,
+        OrbitalElements::circular(10.0, 0.0),
+        GRAVITY,
+    );
+
+    [
+        mass_one, mass_two, mass_three, mass_four, mass_five, mass_six,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mesh_needs_a_power_of_two_cells() {
+        for cells in [0, 1, 2, 3, 6, 100] {
+            let solver = Solver::ParticleMesh { cells };
+            assert_eq!(solver.validate(), Err(InvalidSolver::MeshCells(cells)));
+            let solver = Solver::TreePm { cells, split: 1.0 };
+            assert_eq!(solver.validate(), Err(InvalidSolver::MeshCells(cells)));
+        }
+        for cells in [4, 64, 256] {
+            let solver = Solver::ParticleMesh { cells };
+            assert_eq!(solver.validate(), Ok(solver));
+        }
+        assert_eq!(Solver::BarnesHut.validate(), Ok(Solver::BarnesHut));
+    }
+}
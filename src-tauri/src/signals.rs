@@ -1,32 +1,35 @@
-use crate::{vector::Vector2, Boid, Boundary};
-use std::sync::Arc;
+use crate::{
+    bodies::BodyRef,
+    orbit::{Attractor, OrbitalElements},
+    sph::Gas,
+    vector::Vector2,
+    Boundary,
+};
 
 #[derive(serde::Serialize, Clone, Copy)]
 pub struct Body {
+    pub id: usize,
     pub position: Vector2<f64>,
     pub velocity: Vector2<f64>,
     pub mass: f64,
+    pub charge: f64,
+    pub circulation: f64,
+    pub gas: Option<Gas>,
     pub radius: f64,
 }
 
-impl From<&Boid> for Body {
-    fn from(value: &Boid) -> Self {
-        let lock = value.inner.read().expect("RWLock was poisoned");
+impl Body {
+    /// `heaviest` is the mass of the heaviest body in the snapshot, which sizes the radius.
+    pub fn new(body: BodyRef, heaviest: f64) -> Self {
         Self {
-            position: lock.pos,
-            velocity: lock.velocity,
-            mass: lock.mass,
-            radius: lock.radius(),
-        }
-    }
-}
-impl From<&Arc<Boid>> for Body {
-    fn from(value: &Arc<Boid>) -> Self {
-        Self {
-            position: value.position(),
-            velocity: value.velocity(),
-            mass: value.mass(),
-            radius: value.radius(),
+            id: body.id(),
+            position: body.position(),
+            velocity: body.velocity(),
+            mass: body.mass(),
+            charge: body.charge(),
+            circulation: body.circulation(),
+            gas: body.gas(),
+            radius: body.radius(heaviest),
         }
     }
 }
@@ -54,3 +57,17 @@ impl TreeState {
         }
     }
 }
+
+#[derive(serde::Serialize, Debug, Clone, Copy)]
+pub struct Orbit {
+    /// [`Body::id`] of the orbiting body.
+    pub body: usize,
+    /// What it orbits, with a body given by its id.
+    pub attractor: Attractor,
+    pub elements: Option<OrbitalElements>,
+    /// Orbital period in the scenario's time unit, if the orbit is bound.
+    pub period: Option<f64>,
+    /// Closest and, if the orbit is bound, farthest distance from the attractor.
+    pub periapsis: Option<f64>,
+    pub apoapsis: Option<f64>,
+}
@@ -0,0 +1,311 @@
+//! The Sun, the eight planets and their major moons, projected onto the ecliptic.
+//!
+//! Runs in [`UnitSystem::ASTRONOMICAL`], which keeps every quantity within a few orders of
+//! magnitude of one.
+
+use crate::{
+    boid::Boid,
+    orbit::OrbitalElements,
+    scenario::{satellite, Parameters, Scenario},
+    units::UnitSystem,
+    vector::Vector2,
+};
+
+/// Days advanced each tick, short enough to resolve the orbit of Io.
+const DT: f64 = 0.02;
+const UNITS: UnitSystem = UnitSystem::ASTRONOMICAL;
+
+struct Planet {
+    /// Mass in solar masses.
+    mass: f64,
+    /// Semi-major axis in AU.
+    semi_major_axis: f64,
+    eccentricity: f64,
+    /// Longitude of perihelion in degrees.
+    longitude_of_perihelion: f64,
+    /// Mean longitude at J2000 in degrees.
+    mean_longitude: f64,
+}
+
+struct Moon {
+    /// Index into [`PLANETS`].
+    planet: usize,
+    /// Mass in kilograms.
+    mass: f64,
+    /// Semi-major axis in kilometres.
+    semi_major_axis: f64,
+    eccentricity: f64,
+    /// Starting position round the planet in degrees. These are not ephemeris phases.
+    phase: f64,
+    retrograde: bool,
+}
+
+/// J2000 mean elements from Standish's approximate planetary positions. They follow each planet's
+/// barycentre with its moons, so for Earth they are those of the Earth-Moon barycentre.
+const PLANETS: [Planet; 8] = [
+    // Mercury
+    Planet {
+        mass: 1.0 / 6_023_600.0,
+        semi_major_axis: 0.387_099_27,
+        eccentricity: 0.205_635_93,
+        longitude_of_perihelion: 77.457_796_28,
+        mean_longitude: 252.250_323_50,
+    },
+    // Venus
+    Planet {
+        mass: 1.0 / 408_523.71,
+        semi_major_axis: 0.723_335_66,
+        eccentricity: 0.006_776_72,
+        longitude_of_perihelion: 131.602_467_18,
+        mean_longitude: 181.979_099_50,
+    },
+    // Earth
+    Planet {
+        mass: 1.0 / 332_946.05,
+        semi_major_axis: 1.000_002_61,
+        eccentricity: 0.016_711_23,
+        longitude_of_perihelion: 102.937_681_93,
+        mean_longitude: 100.464_571_66,
+    },
+    // Mars
+    Planet {
+        mass: 1.0 / 3_098_708.0,
+        semi_major_axis: 1.523_710_34,
+        eccentricity: 0.093_394_10,
+        longitude_of_perihelion: -23.943_629_59,
+        mean_longitude: -4.553_432_05,
+    },
+    // Jupiter
+    Planet {
+        mass: 1.0 / 1_047.348_6,
+        semi_major_axis: 5.202_887_00,
+        eccentricity: 0.048_386_24,
+        longitude_of_perihelion: 14.728_479_83,
+        mean_longitude: 34.396_440_51,
+    },
+    // Saturn
+    Planet {
+        mass: 1.0 / 3_497.898,
+        semi_major_axis: 9.536_675_94,
+        eccentricity: 0.053_861_79,
+        longitude_of_perihelion: 92.598_878_31,
+        mean_longitude: 49.954_244_23,
+    },
+    // Uranus
+    Planet {
+        mass: 1.0 / 22_902.98,
+        semi_major_axis: 19.189_164_64,
+        eccentricity: 0.047_257_44,
+        longitude_of_perihelion: 170.954_276_30,
+        mean_longitude: 313.238_104_51,
+    },
+    // Neptune
+    Planet {
+        mass: 1.0 / 19_412.24,
+        semi_major_axis: 30.069_922_76,
+        eccentricity: 0.008_590_48,
+        longitude_of_perihelion: 44.964_762_27,
+        mean_longitude: -55.120_029_69,
+    },
+];
+
+const MOONS: [Moon; 10] = [
+    // The Moon
+    Moon {
+        planet: 2,
+        mass: 7.342e22,
+        semi_major_axis: 384_399.0,
+        eccentricity: 0.0549,
+        phase: 0.0,
+        retrograde: false,
+    },
+    // Io
+    Moon {
+        planet: 4,
+        mass: 8.931_9e22,
+        semi_major_axis: 421_700.0,
+        eccentricity: 0.0041,
+        phase: 0.0,
+        retrograde: false,
+    },
+    // Europa
+    Moon {
+        planet: 4,
+        mass: 4.799_8e22,
+        semi_major_axis: 671_034.0,
+        eccentricity: 0.009,
+        phase: 90.0,
+        retrograde: false,
+    },
+    // Ganymede
+    Moon {
+        planet: 4,
+        mass: 1.481_9e23,
+        semi_major_axis: 1_070_412.0,
+        eccentricity: 0.0013,
+        phase: 180.0,
+        retrograde: false,
+    },
+    // Callisto
+    Moon {
+        planet: 4,
+        mass: 1.075_9e23,
+        semi_major_axis: 1_882_709.0,
+        eccentricity: 0.0074,
+        phase: 270.0,
+        retrograde: false,
+    },
+    // Rhea
+    Moon {
+        planet: 5,
+        mass: 2.306e21,
+        semi_major_axis: 527_108.0,
+        eccentricity: 0.0012,
+        phase: 0.0,
+        retrograde: false,
+    },
+    // Titan
+    Moon {
+        planet: 5,
+        mass: 1.345_2e23,
+        semi_major_axis: 1_221_870.0,
+        eccentricity: 0.0288,
+        phase: 180.0,
+        retrograde: false,
+    },
+    // Titania
+    Moon {
+        planet: 6,
+        mass: 3.527e21,
+        semi_major_axis: 435_910.0,
+        eccentricity: 0.0011,
+        phase: 0.0,
+        retrograde: false,
+    },
+    // Oberon
+    Moon {
+        planet: 6,
+        mass: 3.014e21,
+        semi_major_axis: 583_520.0,
+        eccentricity: 0.0014,
+        phase: 180.0,
+        retrograde: false,
+    },
+    // Triton
+    Moon {
+        planet: 7,
+        mass: 2.139e22,
+        semi_major_axis: 354_759.0,
+        eccentricity: 0.000_016,
+        phase: 0.0,
+        retrograde: true,
+    },
+];
+
+pub fn scenario() -> Scenario {
+    let gravity = UNITS.gravity();
+    let sun = Boid::new(0.0, 0.0, 1.0);
+
+    let mut planets: Vec<_> = PLANETS
+        .iter()
+        .map(|planet| {
+            let elements = OrbitalElements::new(
+                planet.semi_major_axis,
+                planet.eccentricity,
+                planet.longitude_of_perihelion.to_radians(),
+                (planet.mean_longitude - planet.longitude_of_perihelion).to_radians(),
+            );
+            satellite(&sun, planet.mass, elements, gravity)
+        })
+        .collect();
+
+    let moons: Vec<_> = MOONS
+        .iter()
+        .map(|moon| {
+            let mut elements = OrbitalElements::new(
+                UNITS
+                    .length
+                    .si_to_unit(moon.semi_major_axis * 1e3)
+                    .expect("Astronomical units have a size in SI"),
+                moon.eccentricity,
+                0.0,
+                moon.phase.to_radians(),
+            );
+            if moon.retrograde {
+                elements = elements.retrograde();
+            }
+            satellite(
+                &planets[moon.planet],
+                UNITS
+                    .mass
+                    .si_to_unit(moon.mass)
+                    .expect("Astronomical units have a size in SI"),
+                elements,
+                gravity,
+            )
+        })
+        .collect();
+
+    // The moons are placed around where the planets' barycentres should be, so step each planet
+    // back by its moons' share to put the barycentre there.
+    for (moon, body) in MOONS.iter().zip(&moons) {
+        let planet = &mut planets[moon.planet];
+        let share = body.mass() / planet.mass();
+        let offset = body.position() - planet.position();
+        let relative_velocity = body.velocity() - planet.velocity();
+        planet.set_position(planet.position() - offset * share);
+        planet.set_velocity(planet.velocity() - relative_velocity * share);
+    }
+
+    let mut bodies = vec![sun];
+    bodies.extend(planets);
+    bodies.extend(moons);
+
+    // Move into the barycentric frame so the system does not drift across the screen.
+    let total_mass: f64 = bodies.iter().map(Boid::mass).sum();
+    let (weighted_position, momentum) = bodies.iter().fold(
+        (Vector2::default(), Vector2::default()),
+        |(position, momentum), body| {
+            (
+                position + body.position() * body.mass(),
+                momentum + body.velocity() * body.mass(),
+            )
+        },
+    );
+    let barycentre = weighted_position / total_mass;
+    let drift = momentum / total_mass;
+    for body in &mut bodies {
+        body.set_position(body.position() - barycentre);
+        body.set_velocity(body.velocity() - drift);
+    }
+
+    Scenario {
+        bodies,
+        parameters: Parameters::new(UNITS, DT),
+        period: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bodies::Bodies, orbit};
+
+    #[test]
+    fn planets_have_their_sidereal_periods() {
+        // Sidereal periods in days.
+        let periods = [
+            87.969, 224.701, 365.256, 686.980, 4_332.59, 10_759.22, 30_688.5, 60_182.0,
+        ];
+        let bodies: Bodies = scenario().bodies.into_iter().collect();
+        let orbits = orbit::osculating_elements(&bodies, UNITS.gravity());
+        for (orbit, expected) in orbits[1..=PLANETS.len()].iter().zip(periods) {
+            let period = orbit.elements.unwrap().period(orbit.mu).unwrap();
+            // The moons and the other planets perturb the osculating orbits by a part in a thousand.
+            assert!(
+                (period / expected - 1.0).abs() < 2e-3,
+                "{period} against {expected}"
+            );
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! A common interface over the structures that index bodies by position, so that each kind of
+//! interaction can use whichever suits it: the quadtree for gravity, a uniform grid for short-range
+//! neighbour searches, or a k-d tree for clustered bodies.
+
+use crate::{
+    bodies::{Bodies, BodyRef, Particle},
+    boundary::Boundary,
+    force_law::ForceLaw,
+    traits::Intersect,
+    vector::Vector2,
+};
+
+/// A group of bodies seen while walking an index: their total mass, where it is centred, and a
+/// box around them.
+#[derive(Debug, Clone, Copy)]
+pub struct MassGroup<'a, T = Particle> {
+    pub mass: f64,
+    pub center_of_mass: Vector2<f64>,
+    pub bounds: Boundary,
+    /// The body, when the group is a single one.
+    pub body: Option<&'a T>,
+}
+
+/// Which index short-range interactions search for neighbours in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum NeighbourIndex {
+    /// The quadtree built for gravity, so nothing extra is built.
+    Quadtree,
+    /// A hash grid with cells as wide as the longest interaction range.
+    Grid,
+    /// A k-d tree, which stays balanced however the bodies cluster.
+    KdTree,
+}
+
+pub trait SpatialIndex {
+    /// Indexes `bodies`, sizing the structure to fit them.
+    fn build(bodies: &Bodies) -> Self
+    where
+        Self: Sized;
+
+    /// Every body inside `rect`, edges included.
+    fn query_rect(&self, rect: Boundary) -> Vec<Particle>;
+
+    /// Every body within `radius` of `center`.
+    fn query_radius(&self, center: Vector2<f64>, radius: f64) -> Vec<Particle>;
+
+    /// The `k` bodies nearest to `point`, nearest first.
+    fn k_nearest(&self, point: Vector2<f64>, k: usize) -> Vec<Particle>;
+
+    /// Walks the groups of bodies from the coarsest down, going into the parts of a group only
+    /// when `open` returns true for it. Every body is in exactly one group that is not opened.
+    fn visit_mass(&self, open: &mut dyn FnMut(&MassGroup) -> bool);
+}
+
+/// Gravity on `body` from the bodies in any index, replacing each group by its total mass once
+/// its size over its distance is below `theta`.
+pub fn calculate_force<I: SpatialIndex + ?Sized, L: ForceLaw>(
+    index: &I,
+    body: BodyRef,
+    theta: f64,
+    law: &L,
+) -> Vector2<f64> {
+    let position = body.position();
+    let mut force = Vector2::default();
+    index.visit_mass(&mut |group| {
+        let size = (group.bounds.max - group.bounds.min).magnitude();
+        let displacement = group.center_of_mass - position;
+        let far = group.body.is_some()
+            || !group.bounds.intersects(&position) && size < theta * displacement.magnitude();
+        let itself = group.body.is_some_and(|other| other.index == body.index());
+        if far && !itself {
+            force = force + law.force(displacement, body.mass(), group.mass);
+        }
+        !far
+    });
+    force
+}
+
+/// The smallest box around `bodies`, or an empty one at the origin when there are none.
+pub fn bounding_box(bodies: &[Particle]) -> Boundary {
+    let mut positions = bodies.iter().map(|body| body.position);
+    let Some(first) = positions.next() else {
+        return Boundary::from_center(Vector2::default(), 0.0);
+    };
+    positions.fold(
+        Boundary {
+            min: first,
+            max: first,
+        },
+        |bounds, position| Boundary {
+            min: Vector2::new(bounds.min.x.min(position.x), bounds.min.y.min(position.y)),
+            max: Vector2::new(bounds.max.x.max(position.x), bounds.max.y.max(position.y)),
+        },
+    )
+}
+
+/// Total mass of `bodies` and where it is centred.
+pub fn aggregate<'a>(bodies: impl IntoIterator<Item = &'a Particle>) -> (f64, Vector2<f64>) {
+    let (mass, moment) = bodies
+        .into_iter()
+        .fold((0.0, Vector2::default()), |(mass, moment), body| {
+            (mass + body.mass, moment + body.position * body.mass)
+        });
+    if mass == 0.0 {
+        (0.0, moment)
+    } else {
+        (mass, moment / mass)
+    }
+}
+
+/// Keeps the `k` nearest candidates seen so far, sorted by distance.
+pub struct Nearest<T = Particle> {
+    k: usize,
+    found: Vec<(f64, T)>,
+}
+
+impl<T: Clone> Nearest<T> {
+    pub const fn new(k: usize) -> Self {
+        Self {
+            k,
+            found: Vec::new(),
+        }
+    }
+
+    /// Distance within which a candidate could still make the list.
+    pub fn reach(&self) -> f64 {
+        if self.found.len() < self.k {
+            f64::INFINITY
+        } else {
+            self.found
+                .last()
+                .map_or(f64::INFINITY, |(distance, _)| *distance)
+        }
+    }
+
+    pub fn offer(&mut self, distance: f64, body: &T) {
+        if self.k == 0 || distance >= self.reach() {
+            return;
+        }
+        let at = self.found.partition_point(|(other, _)| *other <= distance);
+        self.found.insert(at, (distance, body.clone()));
+        self.found.truncate(self.k);
+    }
+
+    pub fn into_bodies(self) -> Vec<T> {
+        self.found.into_iter().map(|(_, body)| body).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        boid::Boid, grid::UniformGrid, kdtree::KdTree, quadtree::Quadtree, random::Random,
+    };
+
+    /// Two clusters of different sizes, so that the indexes split unevenly.
+    fn clustered(count: usize) -> Bodies {
+        let mut random = Random::new(3);
+        (0..count)
+            .map(|index| {
+                let (center, spread) = if index % 3 == 0 {
+                    (800.0, 20.0)
+                } else {
+                    (200.0, 300.0)
+                };
+                Boid::new(
+                    center + (random.uniform() - 0.5) * spread,
+                    center + (random.uniform() - 0.5) * spread,
+                    1.0,
+                )
+            })
+            .collect()
+    }
+
+    fn indices(mut found: Vec<Particle>) -> Vec<usize> {
+        found.sort_by_key(|body| body.index);
+        found.into_iter().map(|body| body.index).collect()
+    }
+
+    fn matches_brute_force<I: SpatialIndex>(bodies: &Bodies) {
+        let index = I::build(bodies);
+        let particles = bodies.particles();
+        let rects = [
+            Boundary::new(Vector2::new(100.0, 150.0), Vector2::new(300.0, 200.0)),
+            Boundary::new(Vector2::new(790.0, 790.0), Vector2::new(805.0, 810.0)),
+            Boundary::new(Vector2::new(-1e6, -1e6), Vector2::new(1e6, 1e6)),
+            Boundary::new(Vector2::new(2000.0, 2000.0), Vector2::new(3000.0, 3000.0)),
+        ];
+        for rect in rects {
+            let expected = particles
+                .iter()
+                .filter(|body| rect.intersects(&body.position));
+            assert_eq!(
+                indices(index.query_rect(rect)),
+                indices(expected.copied().collect()),
+                "{rect:?}"
+            );
+        }
+        let points = [
+            Vector2::new(200.0, 200.0),
+            Vector2::new(800.0, 790.0),
+            Vector2::new(500.0, -100.0),
+            Vector2::new(-1e5, 3e5),
+        ];
+        for point in points {
+            for radius in [0.0, 10.0, 150.0] {
+                let expected = particles
+                    .iter()
+                    .filter(|body| (body.position - point).magnitude() <= radius);
+                assert_eq!(
+                    indices(index.query_radius(point, radius)),
+                    indices(expected.copied().collect()),
+                    "{point:?} {radius}"
+                );
+            }
+            for k in [0, 1, 7, 40] {
+                let mut expected = particles.clone();
+                expected.sort_by(|a, b| {
+                    let distance = |body: &Particle| (body.position - point).magnitude();
+                    distance(a).total_cmp(&distance(b))
+                });
+                expected.truncate(k);
+                let found = index.k_nearest(point, k);
+                let found: Vec<_> = found.iter().map(|body| body.index).collect();
+                let expected: Vec<_> = expected.iter().map(|body| body.index).collect();
+                assert_eq!(found, expected, "{point:?} {k}");
+            }
+        }
+    }
+
+    #[test]
+    fn indexes_match_brute_force() {
+        let bodies = clustered(600);
+        matches_brute_force::<Quadtree>(&bodies);
+        matches_brute_force::<UniformGrid>(&bodies);
+        matches_brute_force::<KdTree>(&bodies);
+    }
+}
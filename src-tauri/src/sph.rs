@@ -0,0 +1,350 @@
+//! Smoothed-particle hydrodynamics, which turns bodies into parcels of self-gravitating gas.
+//!
+//! Uses the two-dimensional cubic spline kernel, which reaches out to twice the smoothing length.
+
+use std::f64::consts::PI;
+
+use crate::{
+    bodies::Bodies,
+    boid::Boid,
+    scenario::{Parameters, Scenario},
+    spatial::SpatialIndex,
+    units::UnitSystem,
+    vector::Vector2,
+};
+
+/// N-body time advanced each tick.
+const DT: f64 = 1e-3;
+
+/// Hydrodynamic state of a gas particle.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct Gas {
+    pub smoothing_length: f64,
+    pub density: f64,
+    /// Thermal energy per unit mass.
+    pub internal_energy: f64,
+}
+
+impl Gas {
+    /// A particle whose density is found on the first step.
+    pub fn new(smoothing_length: f64, internal_energy: f64) -> Self {
+        Self {
+            smoothing_length,
+            density: 0.0,
+            internal_energy,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum EquationOfState {
+    /// `P = (gamma - 1) rho u`, with the internal energy evolved by compression and viscous heating.
+    IdealGas { gamma: f64 },
+    /// `P = K rho^gamma`, which ignores the internal energy.
+    Polytropic { constant: f64, gamma: f64 },
+}
+
+impl EquationOfState {
+    fn pressure(self, gas: &Gas) -> f64 {
+        match self {
+            Self::IdealGas { gamma } => (gamma - 1.0) * gas.density * gas.internal_energy,
+            Self::Polytropic { constant, gamma } => constant * gas.density.powf(gamma),
+        }
+    }
+
+    fn sound_speed(self, gas: &Gas) -> f64 {
+        let gamma = match self {
+            Self::IdealGas { gamma } | Self::Polytropic { gamma, .. } => gamma,
+        };
+        (gamma * self.pressure(gas) / gas.density).sqrt()
+    }
+}
+
+/// Settings for the gas particles in a scenario.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Sph {
+    pub equation_of_state: EquationOfState,
+    /// Linear term of Monaghan's artificial viscosity, which damps oscillations behind shocks.
+    pub alpha: f64,
+    /// Quadratic term, which stops particles streaming through each other in strong shocks.
+    pub beta: f64,
+    /// How many neighbours each smoothing length is adapted towards.
+    pub neighbours: f64,
+}
+
+/// The cubic spline kernel at `distance` for smoothing length `h`.
+fn kernel(distance: f64, h: f64) -> f64 {
+    let q = distance / h;
+    let normalisation = 10.0 / (7.0 * PI * h * h);
+    if q < 1.0 {
+        normalisation * (1.0 - 1.5 * q * q + 0.75 * q * q * q)
+    } else if q < 2.0 {
+        normalisation * 0.25 * (2.0 - q).powi(3)
+    } else {
+        0.0
+    }
+}
+
+/// Derivative of [`kernel`] with respect to distance.
+fn kernel_derivative(distance: f64, h: f64) -> f64 {
+    let q = distance / h;
+    let normalisation = 10.0 / (7.0 * PI * h * h * h);
+    if q < 1.0 {
+        normalisation * (-3.0 * q + 2.25 * q * q)
+    } else if q < 2.0 {
+        normalisation * -0.75 * (2.0 - q).powi(2)
+    } else {
+        0.0
+    }
+}
+
+impl Sph {
+    /// Adapts the smoothing length of every gas particle in `bodies` towards one that finds about
+    /// [`Sph::neighbours`] of them, then sums its density from its neighbours with the new length.
+    /// Returns the gas state of every body, in order.
+    pub fn update_densities(
+        &self,
+        bodies: &Bodies,
+        neighbours: &dyn SpatialIndex,
+    ) -> Vec<Option<Gas>> {
+        bodies
+            .iter()
+            .map(|body| {
+                let gas = body.gas()?;
+                let position = body.position();
+                // Gas particles within reach of the kernel with smoothing length `h`, and the
+                // density they give.
+                let measure = |h: f64| {
+                    let nearby = neighbours
+                        .query_radius(position, 2.0 * h)
+                        .into_iter()
+                        .filter(|other| bodies.get(other.index).gas().is_some());
+                    nearby.fold((0_usize, 0.0), |(found, density), other| {
+                        let distance = (other.position - position).magnitude();
+                        (found + 1, density + other.mass * kernel(distance, h))
+                    })
+                };
+                let h = gas.smoothing_length;
+                let (found, _) = measure(h);
+                #[allow(clippy::cast_precision_loss)]
+                let found = found as f64;
+                // In two dimensions the neighbour count goes as h^2. Only going halfway keeps the
+                // smoothing length from oscillating, and going at most a factor of two keeps it
+                // finite when nothing is found.
+                let target = (h * (self.neighbours / found).sqrt()).clamp(0.5 * h, 2.0 * h);
+                let smoothing_length = f64::midpoint(h, target);
+                let (_, density) = measure(smoothing_length);
+                Some(Gas {
+                    smoothing_length,
+                    density,
+                    ..gas
+                })
+            })
+            .collect()
+    }
+
+    /// Acceleration of the body at `index` from pressure and viscosity, and the rate its internal
+    /// energy changes, with the gas state of every body in `states`. `reach` must be at least twice
+    /// the largest smoothing length, so that every particle whose kernel overlaps the body is
+    /// found.
+    pub fn calculate_acceleration(
+        &self,
+        bodies: &Bodies,
+        index: usize,
+        states: &[Option<Gas>],
+        neighbours: &dyn SpatialIndex,
+        reach: f64,
+    ) -> (Vector2<f64>, f64) {
+        let Some(gas) = states[index] else {
+            return (Vector2::default(), 0.0);
+        };
+        let body = bodies.get(index);
+        let (position, velocity) = (body.position(), body.velocity());
+        let pressure = self.equation_of_state.pressure(&gas);
+        let sound_speed = self.equation_of_state.sound_speed(&gas);
+
+        let mut acceleration = Vector2::default();
+        let mut heating = 0.0;
+        for other in neighbours.query_radius(position, reach) {
+            let Some(other_gas) = states[other.index] else {
+                continue;
+            };
+            if other.index == index {
+                continue;
+            }
+            let offset = position - other.position;
+            let distance = offset.magnitude();
+            let h = f64::midpoint(gas.smoothing_length, other_gas.smoothing_length);
+            if distance == 0.0 || distance >= 2.0 * h {
+                continue;
+            }
+            let gradient = offset * (kernel_derivative(distance, h) / distance);
+            let relative_velocity = velocity - bodies.get(other.index).velocity();
+
+            let approach = relative_velocity.dot(&offset);
+            let viscosity = if approach < 0.0 {
+                let mu = h * approach / (distance * distance + 0.01 * h * h);
+                let mean_sound_speed =
+                    f64::midpoint(sound_speed, self.equation_of_state.sound_speed(&other_gas));
+                let mean_density = f64::midpoint(gas.density, other_gas.density);
+                (-self.alpha * mean_sound_speed * mu + self.beta * mu * mu) / mean_density
+            } else {
+                0.0
+            };
+
+            let other_pressure = self.equation_of_state.pressure(&other_gas);
+            let term = pressure / (gas.density * gas.density)
+                + other_pressure / (other_gas.density * other_gas.density)
+                + viscosity;
+            acceleration = acceleration - gradient * (other.mass * term);
+            heating += 0.5 * other.mass * term * relative_velocity.dot(&gradient);
+        }
+        (acceleration, heating)
+    }
+}
+
+fn gas_particle(position: Vector2<f64>, velocity: Vector2<f64>, mass: f64, gas: Gas) -> Boid {
+    let mut boid = Boid::new(position.x, position.y, mass);
+    boid.set_velocity(velocity);
+    boid.set_gas(Some(gas));
+    boid
+}
+
+/// Positions `count` points evenly over an annulus between `inner` and `outer` radii, using the
+/// golden angle.
+fn sunflower(count: u32, inner: f64, outer: f64) -> impl Iterator<Item = Vector2<f64>> {
+    let golden_angle = PI * (3.0 - 5.0_f64.sqrt());
+    (0..count).map(move |i| {
+        let fraction = (f64::from(i) + 0.5) / f64::from(count);
+        let radius = (inner * inner + fraction * (outer * outer - inner * inner)).sqrt();
+        Vector2::new(radius, 0.0).rotate(f64::from(i) * golden_angle)
+    })
+}
+
+/// Smoothing length that gives about `neighbours` neighbours at `count` particles spread over
+/// `area`.
+fn smoothing_length(count: u32, area: f64, neighbours: f64) -> f64 {
+    // The kernel covers a disc of radius 2h.
+    (neighbours * area / f64::from(count) / (4.0 * PI)).sqrt()
+}
+
+const NEIGHBOURS: f64 = 30.0;
+
+fn scenario(
+    bodies: Vec<Boid>,
+    equation_of_state: EquationOfState,
+    smoothing_length: f64,
+) -> Scenario {
+    Scenario {
+        bodies,
+        parameters: Parameters::new(UnitSystem::NBODY, DT)
+            // Pressure can only hold particles apart down to about a smoothing length, so gravity
+            // needs softening on the same scale or close pairs are flung out.
+            .with_softening(smoothing_length)
+            .with_sph(Sph {
+                equation_of_state,
+                alpha: 1.0,
+                beta: 2.0,
+                neighbours: NEIGHBOURS,
+            }),
+        period: None,
+    }
+}
+
+/// A cold, slowly rotating cloud of unit mass and radius, which collapses under its own gravity
+/// until pressure and rotation halt it in a hot central clump.
+pub fn protostellar_collapse(count: u32) -> Scenario {
+    const INTERNAL_ENERGY: f64 = 0.05;
+    // A small fraction of the angular velocity that would hold the edge up.
+    const SPIN: f64 = 0.2;
+    let mass = 1.0 / f64::from(count);
+    let h = smoothing_length(count, PI, NEIGHBOURS);
+    let bodies = sunflower(count, 0.0, 1.0)
+        .map(|position| {
+            let velocity = Vector2::new(-position.y, position.x) * SPIN;
+            gas_particle(position, velocity, mass, Gas::new(h, INTERNAL_ENERGY))
+        })
+        .collect();
+    scenario(bodies, EquationOfState::IdealGas { gamma: 5.0 / 3.0 }, h)
+}
+
+/// A light, warm gas disc in Keplerian rotation around a star of unit mass.
+pub fn gas_disk(count: u32) -> Scenario {
+    const INNER: f64 = 0.5;
+    const OUTER: f64 = 2.0;
+    const DISK_MASS: f64 = 0.01;
+    let mass = DISK_MASS / f64::from(count);
+    let h = smoothing_length(count, PI * (OUTER * OUTER - INNER * INNER), NEIGHBOURS);
+    let mut bodies = vec![Boid::new(0.0, 0.0, 1.0)];
+    bodies.extend(sunflower(count, INNER, OUTER).map(|position| {
+        let radius = position.magnitude();
+        let speed = (1.0 / radius).sqrt();
+        let velocity = Vector2::new(-position.y, position.x) * (speed / radius);
+        // Pressure makes the gas orbit slightly slower than Keplerian, which this ignores.
+        gas_particle(position, velocity, mass, Gas::new(h, 0.0))
+    }));
+    // Isothermal, with a sound speed a twentieth of the orbital speed at the inner edge.
+    let sound_speed = (1.0 / INNER).sqrt() / 20.0;
+    scenario(
+        bodies,
+        EquationOfState::Polytropic {
+            constant: sound_speed * sound_speed,
+            gamma: 1.0,
+        },
+        h,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quadtree::Quadtree;
+
+    fn sph(neighbours: f64) -> Sph {
+        Sph {
+            equation_of_state: EquationOfState::IdealGas { gamma: 5.0 / 3.0 },
+            alpha: 1.0,
+            beta: 2.0,
+            neighbours,
+        }
+    }
+
+    #[test]
+    fn density_uses_the_new_smoothing_length() {
+        let gas = Gas::new(1.0, 1.0);
+        let bodies: Bodies = [gas_particle(
+            Vector2::default(),
+            Vector2::default(),
+            2.0,
+            gas,
+        )]
+        .into_iter()
+        .collect();
+        let states = sph(20.0).update_densities(&bodies, &Quadtree::build(&bodies));
+        let gas = states[0].expect("The body is gas");
+        // Alone, it wants many more neighbours, so the length grows by as much as it can.
+        assert!((gas.smoothing_length - 1.5).abs() < 1e-12, "{gas:?}");
+        assert!(
+            (gas.density - 2.0 * kernel(0.0, 1.5)).abs() < 1e-12,
+            "{gas:?}"
+        );
+    }
+
+    #[test]
+    fn smoothing_length_stays_finite_without_neighbours() {
+        let gas = Gas::new(1.0, 1.0);
+        let bodies: Bodies = [gas_particle(
+            Vector2::default(),
+            Vector2::default(),
+            2.0,
+            gas,
+        )]
+        .into_iter()
+        .collect();
+        // An index over none of the bodies finds nothing, not even the body itself.
+        let states = sph(20.0).update_densities(&bodies, &Quadtree::build(&Bodies::default()));
+        let gas = states[0].expect("The body is gas");
+        assert!(gas.smoothing_length.is_finite(), "{gas:?}");
+        assert!(gas.density.abs() < 1e-12, "{gas:?}");
+    }
+}
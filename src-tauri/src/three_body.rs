@@ -0,0 +1,206 @@
+//! Three-body configurations with known behaviour, used as regression benchmarks for integrators.
+//!
+//! All of them run in N-body units where `G = 1`, and the periodic ones come with the period after
+//! which every body should be back at its starting position and velocity.
+
+use std::f64::consts::TAU;
+
+use crate::{
+    boid::Boid,
+    scenario::{Parameters, Scenario, Solver},
+    units::UnitSystem,
+    vector::Vector2,
+};
+
+/// N-body time advanced each tick.
+const DT: f64 = 1e-3;
+
+fn body(mass: f64, position: Vector2<f64>, velocity: Vector2<f64>) -> Boid {
+    let mut boid = Boid::new(position.x, position.y, mass);
+    boid.set_velocity(velocity);
+    boid
+}
+
+fn scenario(bodies: Vec<Boid>, period: Option<f64>) -> Scenario {
+    Scenario {
+        bodies,
+        // With only three bodies exact forces are cheap, and the tree's error would swamp the
+        // integrator's.
+        parameters: Parameters::new(UnitSystem::NBODY, DT).with_solver(Solver::Direct),
+        period,
+    }
+}
+
+/// The figure-eight choreography of Chenciner and Montgomery, with Simó's initial conditions.
+pub fn figure_eight() -> Scenario {
+    let position = Vector2::new(0.970_004_36, -0.243_087_53);
+    let velocity = Vector2::new(-0.932_407_37, -0.864_731_46);
+    scenario(
+        vec![
+            body(1.0, position, velocity / -2.0),
+            body(1.0, position * -1.0, velocity / -2.0),
+            body(1.0, Vector2::default(), velocity),
+        ],
+        Some(6.325_913_98),
+    )
+}
+
+/// Lagrange's solution: three equal masses rigidly rotating at the corners of an equilateral
+/// triangle inscribed in the unit circle.
+pub fn lagrange() -> Scenario {
+    // Each side is sqrt(3), so the angular velocity is sqrt(G * 3m / side^3) = 3^(-1/4).
+    let angular_velocity = 3.0_f64.powf(-0.25);
+    let bodies = (0..3)
+        .map(|i| {
+            let position = Vector2::new(1.0, 0.0).rotate(TAU * f64::from(i) / 3.0);
+            let velocity = Vector2::new(-position.y, position.x) * angular_velocity;
+            body(1.0, position, velocity)
+        })
+        .collect();
+    scenario(bodies, Some(TAU / angular_velocity))
+}
+
+/// Euler's collinear solution: a stationary mass midway between two that circle it. The
+/// configuration is unstable, so it only holds for a few periods.
+pub fn euler() -> Scenario {
+    // Each outer mass feels G m^2 (1 + 1/4) at unit distance from the centre.
+    let angular_velocity = 1.25_f64.sqrt();
+    scenario(
+        vec![
+            body(
+                1.0,
+                Vector2::new(-1.0, 0.0),
+                Vector2::new(0.0, -angular_velocity),
+            ),
+            body(1.0, Vector2::default(), Vector2::default()),
+            body(
+                1.0,
+                Vector2::new(1.0, 0.0),
+                Vector2::new(0.0, angular_velocity),
+            ),
+        ],
+        Some(TAU / angular_velocity),
+    )
+}
+
+/// Burrau's Pythagorean problem: masses 3, 4 and 5 at rest on the corners of a 3-4-5 triangle.
+///
+/// It is not periodic. After a series of very close encounters, which need a much smaller step than
+/// [`DT`] to follow accurately, the lightest body escapes and the other two leave as a binary.
+pub fn pythagorean() -> Scenario {
+    scenario(
+        vec![
+            body(3.0, Vector2::new(1.0, 3.0), Vector2::default()),
+            body(4.0, Vector2::new(-2.0, -1.0), Vector2::default()),
+            body(5.0, Vector2::new(1.0, -1.0), Vector2::default()),
+        ],
+        None,
+    )
+}
+
+/// A Broucke periodic orbit: three equal masses starting on the x axis moving parallel to the
+/// y axis.
+fn broucke(positions: [f64; 3], velocities: [f64; 3], period: f64) -> Scenario {
+    let bodies = positions
+        .iter()
+        .zip(velocities)
+        .map(|(&x, vy)| body(1.0, Vector2::new(x, 0.0), Vector2::new(0.0, vy)))
+        .collect();
+    scenario(bodies, Some(period))
+}
+
+pub fn broucke_a1() -> Scenario {
+    broucke(
+        [-0.989_262_004_3, 2.209_617_724_1, -1.220_355_719_7],
+        [1.916_924_418_5, 0.191_026_873_8, -2.107_951_292_4],
+        6.283_213,
+    )
+}
+
+pub fn broucke_a2() -> Scenario {
+    broucke(
+        [0.336_130_095_0, 0.769_989_380_4, -1.106_119_475_3],
+        [1.532_431_537_0, -0.628_735_097_8, -0.903_696_439_1],
+        7.702_408,
+    )
+}
+
+/// Broucke's R1 orbit, whose close approaches need a much smaller step than [`DT`] to follow.
+/// After one period at [`DT`] the bodies are about a third of a unit from where they started.
+pub fn broucke_r1() -> Scenario {
+    broucke(
+        [0.808_310_623_0, -0.495_414_856_6, -0.312_895_766_4],
+        [0.990_197_916_6, -2.717_143_176_8, 1.726_945_260_2],
+        5.226_525,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Farthest any body ends up from where it started after one period, stepping every pair
+    /// directly with the simulation's integrator.
+    fn return_error(scenario: Scenario) -> f64 {
+        let period = scenario.period.expect("The configuration is periodic");
+        let (gravity, dt) = (scenario.parameters.gravity(), scenario.parameters.dt);
+        let mut bodies = scenario.bodies;
+        let start: Vec<_> = bodies.iter().map(Boid::position).collect();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let ticks = (period / dt).round() as usize;
+        for _ in 0..ticks {
+            let forces: Vec<_> = bodies
+                .iter()
+                .enumerate()
+                .map(|(index, body)| {
+                    bodies
+                        .iter()
+                        .enumerate()
+                        .filter(|&(other, _)| other != index)
+                        .fold(Vector2::default(), |force, (_, other)| {
+                            let direction = other.position() - body.position();
+                            let distance = direction.magnitude();
+                            force
+                                + direction
+                                    * (gravity * body.mass() * other.mass() / distance.powi(3))
+                        })
+                })
+                .collect();
+            for (body, force) in bodies.iter_mut().zip(forces) {
+                let velocity = body.velocity() + force * (dt / body.mass());
+                body.set_velocity(velocity);
+                body.set_position(body.position() + velocity * dt);
+            }
+        }
+        start
+            .iter()
+            .zip(&bodies)
+            .map(|(&start, body)| (body.position() - start).magnitude())
+            .fold(0.0, f64::max)
+    }
+
+    /// Each configuration is back within these of where it started after one period at [`DT`].
+    /// The integrator is first order, so the errors shrink in proportion to the step.
+    #[test]
+    fn periodic_orbits_return_to_the_start() {
+        for (name, scenario, tolerance) in [
+            ("figure eight", figure_eight(), 5e-3),
+            ("Lagrange", lagrange(), 5e-4),
+            ("Euler", euler(), 5e-4),
+            ("Broucke A1", broucke_a1(), 5e-2),
+            ("Broucke A2", broucke_a2(), 1e-3),
+            ("Broucke R1", broucke_r1(), 0.5),
+        ] {
+            let error = return_error(scenario);
+            assert!(error < tolerance, "{name}: {error}");
+        }
+    }
+
+    #[test]
+    fn broucke_r1_converges_with_a_smaller_step() {
+        let mut scenario = broucke_r1();
+        scenario.parameters.dt = DT / 10.0;
+        let error = return_error(scenario);
+        assert!(error < 0.1, "{error}");
+    }
+}
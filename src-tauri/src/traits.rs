@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use crate::vector::Vector2;
 
 pub trait Intersect<T> {
@@ -8,3 +10,24 @@ pub trait Mass {
     fn mass(&self) -> f64;
     fn center_of_mass(&self) -> Vector2<f64>;
 }
+
+/// Something with a place in the plane, which spatial structures can index.
+pub trait Position {
+    fn position(&self) -> Vector2<f64>;
+}
+
+impl<T: Mass + ?Sized> Mass for Arc<T> {
+    fn mass(&self) -> f64 {
+        (**self).mass()
+    }
+
+    fn center_of_mass(&self) -> Vector2<f64> {
+        (**self).center_of_mass()
+    }
+}
+
+impl<T: Position + ?Sized> Position for Arc<T> {
+    fn position(&self) -> Vector2<f64> {
+        (**self).position()
+    }
+}
@@ -1,4 +0,0 @@
-use crate::boid::Boid;
-use std::sync::Arc;
-
-pub type BoidRCell = Arc<Boid>;
@@ -0,0 +1,113 @@
+//! Units the simulation's numbers are expressed in.
+//!
+//! Every scenario picks a [`UnitSystem`]; positions, masses and times inside the simulation are
+//! multiples of its units, and the gravitational constant is derived from them. Reduced units have
+//! no size in SI and describe configurations that are the same at any scale.
+
+use crate::GRAVITY;
+
+const METRES_PER_AU: f64 = 1.495_978_707e11;
+const KILOGRAMS_PER_SOLAR_MASS: f64 = 1.988_47e30;
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct Unit {
+    pub name: &'static str,
+    /// Size of one unit in the matching SI unit, or `None` for a reduced unit, which is measured
+    /// against a scale of the scenario's own and has no fixed size.
+    pub si: Option<f64>,
+}
+
+impl Unit {
+    pub const fn new(name: &'static str, si: f64) -> Self {
+        Self { name, si: Some(si) }
+    }
+
+    pub const fn reduced(name: &'static str) -> Self {
+        Self { name, si: None }
+    }
+
+    pub fn si_to_unit(self, value: f64) -> Option<f64> {
+        self.si.map(|si| value / si)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct UnitSystem {
+    pub length: Unit,
+    pub mass: Unit,
+    pub time: Unit,
+}
+
+impl UnitSystem {
+    /// Metres, kilograms and seconds.
+    pub const SI: Self = Self {
+        length: Unit::new("m", 1.0),
+        mass: Unit::new("kg", 1.0),
+        time: Unit::new("s", 1.0),
+    };
+
+    /// Astronomical units, solar masses and days.
+    pub const ASTRONOMICAL: Self = Self {
+        length: Unit::new("AU", METRES_PER_AU),
+        mass: Unit::new("M☉", KILOGRAMS_PER_SOLAR_MASS),
+        time: Unit::new("d", SECONDS_PER_DAY),
+    };
+
+    /// Hénon N-body units without a physical scale, for configurations that only fix `G = 1`.
+    pub const NBODY: Self = Self {
+        length: Unit::reduced("N-body length"),
+        mass: Unit::reduced("N-body mass"),
+        time: Unit::reduced("N-body time"),
+    };
+
+    /// Lennard-Jones reduced units: the length `σ` and mass `m` of the particles, and the time
+    /// `τ = σ sqrt(m / ε)` they set with the well depth.
+    pub const LENNARD_JONES: Self = Self {
+        length: Unit::reduced("σ"),
+        mass: Unit::reduced("m"),
+        time: Unit::reduced("τ"),
+    };
+
+    /// The gravitational constant expressed in these units. Reduced units follow the N-body
+    /// convention that it is one.
+    pub fn gravity(&self) -> f64 {
+        match (self.length.si, self.mass.si, self.time.si) {
+            (Some(length), Some(mass), Some(time)) => {
+                GRAVITY * mass * time.powi(2) / length.powi(3)
+            }
+            _ => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relative_error(value: f64, expected: f64) -> f64 {
+        (value / expected - 1.0).abs()
+    }
+
+    #[test]
+    fn conversions_from_si() {
+        let au = UnitSystem::ASTRONOMICAL.length;
+        assert!(relative_error(au.si_to_unit(METRES_PER_AU / 4.0).unwrap(), 0.25) < 1e-15);
+        let day = UnitSystem::ASTRONOMICAL.time;
+        assert!(relative_error(day.si_to_unit(365.25 * 86_400.0).unwrap(), 365.25) < 1e-15);
+        let kilogram = UnitSystem::SI.mass;
+        assert!(relative_error(kilogram.si_to_unit(7.0).unwrap(), 7.0) < f64::EPSILON);
+        assert_eq!(UnitSystem::LENNARD_JONES.length.si_to_unit(1.0), None);
+        assert_eq!(UnitSystem::NBODY.mass.si_to_unit(1.0), None);
+    }
+
+    #[test]
+    fn gravity_in_each_system() {
+        assert!(relative_error(UnitSystem::SI.gravity(), GRAVITY) < 1e-15);
+        // The square of Gauss's gravitational constant, in AU^3 / (M☉ d^2).
+        let gauss = 0.017_202_098_95_f64.powi(2);
+        assert!(relative_error(UnitSystem::ASTRONOMICAL.gravity(), gauss) < 1e-4);
+        assert!(relative_error(UnitSystem::NBODY.gravity(), 1.0) < f64::EPSILON);
+        assert!(relative_error(UnitSystem::LENNARD_JONES.gravity(), 1.0) < f64::EPSILON);
+    }
+}
@@ -1,6 +1,6 @@
 use std::ops::{Add, Div, Mul, Sub};
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, serde::Serialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, serde::Deserialize, serde::Serialize)]
 pub struct Vector2<T>
 where
     T: Copy + Clone + PartialEq,
@@ -28,6 +28,28 @@ where
     }
 }
 
+impl Vector2<f64> {
+    pub fn dot(&self, other: &Self) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// The z component of the cross product of the two vectors extended into 3D.
+    pub fn cross(&self, other: &Self) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+
+    /// Rotates the vector anticlockwise by `angle` radians.
+    pub fn rotate(&self, angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+
+    /// The angle of the vector from the positive x axis, in radians.
+    pub fn angle(&self) -> f64 {
+        self.y.atan2(self.x)
+    }
+}
+
 impl<T> Sub for Vector2<T>
 where
     T: Copy + Clone + PartialEq + Sub<Output = T>,
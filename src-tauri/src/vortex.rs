@@ -0,0 +1,142 @@
+//! Point vortices in an ideal two-dimensional fluid, which move with the velocity the others induce
+//! rather than accelerating under forces.
+
+use std::f64::consts::{PI, TAU};
+
+use crate::{
+    boid::Boid,
+    scenario::{Parameters, Scenario},
+    units::UnitSystem,
+    vector::Vector2,
+    DT,
+};
+
+/// Vortices all have the same mass, which plays no part in the flow.
+const VORTEX_MASS: f64 = 1.0;
+
+/// The Biot–Savart law for point vortices, softened into vortex blobs so that the flow stays finite
+/// near each core.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct PointVortices {
+    pub softening: f64,
+}
+
+impl PointVortices {
+    /// Velocity induced at a point by a vortex of `circulation` at `displacement` from it.
+    pub fn induced_velocity(self, displacement: Vector2<f64>, circulation: f64) -> Vector2<f64> {
+        let softened = displacement.dot(&displacement) + self.softening * self.softening;
+        if softened == 0.0 {
+            return Vector2::default();
+        }
+        // The flow circles the vortex, anticlockwise for positive circulation.
+        Vector2::new(displacement.y, -displacement.x) * (circulation / (TAU * softened))
+    }
+}
+
+fn vortex(position: Vector2<f64>, circulation: f64) -> Boid {
+    let mut boid = Boid::new(position.x, position.y, VORTEX_MASS);
+    boid.set_circulation(circulation);
+    boid
+}
+
+fn scenario(bodies: Vec<Boid>, softening: f64) -> Scenario {
+    Scenario {
+        bodies,
+        parameters: Parameters::new(UnitSystem::SI, DT)
+            .with_point_vortices(PointVortices { softening }),
+        period: None,
+    }
+}
+
+/// A von Kármán vortex street: two staggered rows of `count` opposite vortices, spaced in von
+/// Kármán's stable ratio, which travel together as the wake behind a cylinder does.
+pub fn street(count: u32) -> Scenario {
+    const SPACING: f64 = 1.0;
+    const ROW_GAP: f64 = 0.281 * SPACING;
+    let bodies = (0..count)
+        .flat_map(|i| {
+            let x = f64::from(i) * SPACING;
+            [
+                vortex(Vector2::new(x, ROW_GAP / 2.0), -1.0),
+                vortex(Vector2::new(x + SPACING / 2.0, -ROW_GAP / 2.0), 1.0),
+            ]
+        })
+        .collect();
+    scenario(bodies, 0.01)
+}
+
+/// A shear layer of `count` like vortices along a slightly wavy line. The Kelvin–Helmholtz
+/// instability grows the waves until the layer rolls up into a row of large eddies.
+pub fn shear_layer(count: u32) -> Scenario {
+    const LENGTH: f64 = 10.0;
+    const WAVELENGTH: f64 = 2.5;
+    const AMPLITUDE: f64 = 0.05;
+    let spacing = LENGTH / f64::from(count);
+    let bodies = (0..count)
+        .map(|i| {
+            let x = (f64::from(i) + 0.5) * spacing - LENGTH / 2.0;
+            let y = AMPLITUDE * (2.0 * PI * x / WAVELENGTH).sin();
+            // Spreads a velocity jump of 2 across the layer.
+            vortex(Vector2::new(x, y), 2.0 * spacing)
+        })
+        .collect();
+    // Softening over a couple of spacings keeps the roll-up smooth rather than chaotic.
+    scenario(bodies, 2.0 * spacing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::run;
+
+    /// Two vortices a unit apart, stepped finely enough that the first-order advection is close to
+    /// the exact flow.
+    fn pair(circulations: [f64; 2]) -> Scenario {
+        let mut scenario = scenario(
+            vec![
+                vortex(Vector2::new(0.0, 0.0), circulations[0]),
+                vortex(Vector2::new(1.0, 0.0), circulations[1]),
+            ],
+            0.0,
+        );
+        scenario.parameters.dt = 1e-3;
+        scenario
+    }
+
+    #[test]
+    fn like_vortices_co_rotate() {
+        let scenario = pair([TAU, TAU]);
+        // Each moves at Γ / 2πd on a circle of radius d / 2, so the pair turns at Γ / πd².
+        let rate = TAU / PI;
+        let ticks = 500;
+        #[allow(clippy::cast_precision_loss)]
+        let angle = rate * scenario.parameters.dt * ticks as f64;
+        let bodies = run(scenario, ticks);
+        let separation = bodies.positions()[1] - bodies.positions()[0];
+        assert!(
+            (separation.magnitude() - 1.0).abs() < 1e-2,
+            "{separation:?}"
+        );
+        assert!((separation.angle() - angle).abs() < 1e-2, "{separation:?}");
+        let center = (bodies.positions()[0] + bodies.positions()[1]) / 2.0;
+        assert!(
+            (center - Vector2::new(0.5, 0.0)).magnitude() < 1e-9,
+            "{center:?}"
+        );
+    }
+
+    #[test]
+    fn opposite_vortices_translate() {
+        let scenario = pair([TAU, -TAU]);
+        // Each carries the other along at Γ / 2πd, at right angles to the line between them.
+        let speed = 1.0;
+        let ticks = 1_000;
+        #[allow(clippy::cast_precision_loss)]
+        let distance = speed * scenario.parameters.dt * ticks as f64;
+        let bodies = run(scenario, ticks);
+        for (position, start) in bodies.positions().iter().zip([0.0, 1.0]) {
+            let expected = Vector2::new(start, distance);
+            assert!((*position - expected).magnitude() < 1e-9, "{position:?}");
+        }
+    }
+}
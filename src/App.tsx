@@ -1,12 +1,24 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/tauri';
 import Canvas from './components/Canvas';
-import { Body, Tree } from './types';
+import { Body, Preset, Tree, UnitSystem } from './types';
 import './App.css';
 
 function App() {
     const [bodies, setBodies] = useState<Body[]>([]);
     const [tree, setTree] = useState<Tree>({boundaries: [], center_of_mass: {x: 0, y: 0}});
+    const [units, setUnits] = useState<UnitSystem | null>(null);
+    const [period, setPeriod] = useState<number | null>(null);
+    const [temperature, setTemperature] = useState<number>(0);
+
+    async function fetchUnits() {
+        try {
+            const result = await invoke<UnitSystem>('get_units');
+            setUnits(result);
+        } catch (error) {
+            console.error('Failed to fetch units:', error);
+        }
+    }
 
     useEffect(() => {
         async function fetchBodies() {
@@ -26,9 +38,18 @@ function App() {
             }
         }
 
+        async function fetchTemperature() {
+            try {
+                setTemperature(await invoke<number>('get_temperature'));
+            } catch (error) {
+                console.error('Failed to fetch temperature:', error);
+            }
+        }
+
         async function update() {
             await fetchBodies();
             await fetchTree();
+            await fetchTemperature();
         }
 
         const animate = () => {
@@ -36,6 +57,7 @@ function App() {
             requestAnimationFrame(animate);
         }
 
+        fetchUnits();
         requestAnimationFrame(animate);
         return () => {
             // @ts-ignore
@@ -44,10 +66,43 @@ function App() {
 
     }, []);
 
+    const loadPreset = async (preset: Preset) => {
+        try {
+            setPeriod(await invoke<number | null>('load_preset', { preset }));
+            await fetchUnits();
+        } catch (error) {
+            console.error('Failed to load preset:', error);
+        }
+    };
+
     return (
         <>
         <h1>N-Body Problem</h1>
-            <Canvas bodies={bodies} tree={tree} />
+            <select onChange={event => loadPreset(event.target.value as Preset)}>
+                <option value="StableOrbits">Stable orbits</option>
+                <option value="SolarSystem">Solar system</option>
+                <option value="FigureEight">Figure-eight</option>
+                <option value="Lagrange">Lagrange triangle</option>
+                <option value="Euler">Euler collinear</option>
+                <option value="Pythagorean">Pythagorean</option>
+                <option value="BrouckeA1">Broucke A1</option>
+                <option value="BrouckeA2">Broucke A2</option>
+                <option value="BrouckeR1">Broucke R1</option>
+                <option value="PlasmaBlob">Plasma blob</option>
+                <option value="IonCrystal">Ion crystal</option>
+                <option value="ExBDrift">E×B drift</option>
+                <option value="PenningTrap">Penning trap</option>
+                <option value="Flock">Flock</option>
+                <option value="VortexStreet">Vortex street</option>
+                <option value="KelvinHelmholtz">Kelvin–Helmholtz</option>
+                <option value="LennardJonesFluid">Lennard-Jones fluid</option>
+                <option value="ProtostellarCollapse">Protostellar collapse</option>
+                <option value="GasDisk">Gas disk</option>
+                <option value="UniformCollapse">Uniform collapse</option>
+            </select>
+            {period !== null && <span>Expected period: {period.toPrecision(6)} {units?.time.name}</span>}
+            <span>Temperature (k<sub>B</sub>T): {temperature.toPrecision(3)}</span>
+            <Canvas bodies={bodies} tree={tree} units={units} />
         </>
     );
 }
@@ -1,12 +1,13 @@
 import React, { useEffect, useRef } from 'react';
-import { Body, Boundary, Tree, Vec } from '../types';
+import { Body, Boundary, Tree, UnitSystem, Vec } from '../types';
 
 type CanvasProps = {
     bodies: Body[],
-    tree: Tree
+    tree: Tree,
+    units: UnitSystem | null
 }
 
-const Canvas: React.FC<CanvasProps> = ({ bodies, tree }) => {
+const Canvas: React.FC<CanvasProps> = ({ bodies, tree, units }) => {
     const canvasRef = useRef<HTMLCanvasElement | null>(null);
 
     useEffect(() => {
@@ -21,25 +22,25 @@ const Canvas: React.FC<CanvasProps> = ({ bodies, tree }) => {
         canvas.height = window.innerHeight;
 
         // Clear the canvas
-        const draw = (body: Body): void => {
+        const draw = (body: Body, bounds: Boundary): void => {
             context.beginPath();
-            context.fillStyle = 'red';
-            context.strokeStyle = 'red';
-            const x = body.position.x;
-            const y = body.position.y;
+            const colour = body.charge < 0 || body.circulation < 0 ? 'blue' : 'red';
+            context.fillStyle = colour;
+            context.strokeStyle = colour;
+            const { x, y } = translate_and_scale(body.position, bounds);
             const radius = body.radius;
             context.arc(x, y, radius / 3, 0, 2  * Math.PI);
-            context.fillText(`${x.toFixed()}, ${y.toFixed()}`, x, y)
+            context.fillText(`${body.position.x.toPrecision(3)}, ${body.position.y.toPrecision(3)}`, x, y)
             context.fill();
         }
 
-        const drawBoundary = (bound: Boundary): void => {
-            const width = bound.max.x - bound.min.x;
-            const height = bound.max.y - bound.min.y;
-            
+        const drawBoundary = (bound: Boundary, bounds: Boundary): void => {
+            const min = translate_and_scale(bound.min, bounds);
+            const max = translate_and_scale(bound.max, bounds);
+
             context.strokeStyle = "grey";
             context.beginPath();
-            context.strokeRect(bound.min.x, bound.min.y, width, height);
+            context.strokeRect(min.x, min.y, max.x - min.x, max.y - min.y);
         }
 
         const drawCom = (tree: Tree): void => {
@@ -54,42 +55,44 @@ const Canvas: React.FC<CanvasProps> = ({ bodies, tree }) => {
             context.stroke();
         }
 
+        // Scenarios use very different length units, so fit the tree's bounds to the canvas.
         const translate_and_scale = (pos: Vec, bounds: Boundary): Vec => {
             const min = bounds.min;
             const max = bounds.max;
-            const scale = {
-                x: canvas.width / (max.x - min.x),
-                y: canvas.height / (max.y - min.y)
-            };
+            const scale = Math.min(
+                canvas.width / (max.x - min.x),
+                canvas.height / (max.y - min.y)
+            );
 
             return {
-                x: (pos.x - bounds.min.x) * scale.x,
-                y: (pos.y - bounds.min.y) * scale.y
+                x: (pos.x - bounds.min.x) * scale,
+                y: (pos.y - bounds.min.y) * scale
             }
         }
 
         const update = () => {
             context.clearRect(0, 0, canvas.width, canvas.height);
             let boundary = tree.outer_bounds;
-
+            if (!boundary) return;
 
             context.beginPath();
-            context.fillText(`${boundary.min.x.toFixed()} -> ${boundary.max.x.toFixed()}`, 50, 50);
-            context.fillText(`${boundary.min.y.toFixed()} -> ${boundary.max.y.toFixed()}`, 50, 100);
+            const length = units ? ` ${units.length.name}` : '';
+            context.fillText(`${boundary.min.x.toPrecision(3)} -> ${boundary.max.x.toPrecision(3)}${length}`, 50, 50);
+            context.fillText(`${boundary.min.y.toPrecision(3)} -> ${boundary.max.y.toPrecision(3)}${length}`, 50, 100);
 
             bodies.forEach(body => {
-                draw(body);
+                draw(body, boundary);
             });
 
             tree.boundaries.forEach(bound => {
-                drawBoundary(bound);
+                drawBoundary(bound, boundary);
             });
         }
 
         update()
 
         // Draw bodies
-    }, [bodies, tree]);
+    }, [bodies, tree, units]);
 
 
     return <canvas ref={canvasRef} />;
@@ -4,9 +4,13 @@ export type Vec = {
 }
 
 export type Body = {
+    id: number,
     position: Vec,
     velocity: Vec,
     mass: number,
+    charge: number,
+    circulation: number,
+    gas: Gas | null,
     radius: number,
 }
 
@@ -21,3 +25,134 @@ export type Tree = {
     outer_bounds: Boundary,
     center: Vec,
 }
+
+export type Attractor = { Body: number } | "Barycentre"
+
+export type OrbitalElements = {
+    semi_major_axis: number,
+    eccentricity: number,
+    argument_of_periapsis: number,
+    mean_anomaly: number,
+    direction: "Prograde" | "Retrograde",
+}
+
+export type Orbit = {
+    body: number,
+    attractor: Attractor,
+    elements: OrbitalElements | null,
+    period: number | null,
+    periapsis: number | null,
+    apoapsis: number | null,
+}
+
+export type Preset =
+    | "StableOrbits"
+    | "SolarSystem"
+    | "FigureEight"
+    | "Lagrange"
+    | "Euler"
+    | "Pythagorean"
+    | "BrouckeA1"
+    | "BrouckeA2"
+    | "BrouckeR1"
+    | "PlasmaBlob"
+    | "IonCrystal"
+    | "ExBDrift"
+    | "PenningTrap"
+    | "Flock"
+    | "VortexStreet"
+    | "KelvinHelmholtz"
+    | "LennardJonesFluid"
+    | "ProtostellarCollapse"
+    | "GasDisk"
+    | "UniformCollapse"
+
+export type Unit = {
+    name: string,
+    si: number | null,
+}
+
+export type UnitSystem = {
+    length: Unit,
+    mass: Unit,
+    time: Unit,
+}
+
+export type ForceLawKind =
+    | "Newtonian"
+    | { Logarithmic: { length: number } }
+    | { Yukawa: { length: number } }
+    | { Mond: { acceleration: number } }
+
+export type ExpansionOrder = "Monopole" | "Quadrupole"
+
+export type OpeningCriterion =
+    | { Geometric: { theta: number } }
+    | { SalmonWarren: { theta: number } }
+    | { Relative: { tolerance: number, theta: number } }
+
+export type Solver =
+    | "BarnesHut"
+    | "Direct"
+    | { ParticleMesh: { cells: number } }
+    | { TreePm: { cells: number, split: number } }
+    | { Fmm: { order: number } }
+    | "KdTree"
+
+export type NeighbourIndex = "Quadtree" | "Grid" | "KdTree"
+
+export type Coulomb = {
+    constant: number,
+    softening: number,
+}
+
+export type MagneticField =
+    | { Uniform: { strength: number } }
+    | { Gradient: { strength: number, gradient: Vec } }
+
+export type ElectricField =
+    | { Uniform: { field: Vec } }
+    | { Quadrupole: { strength: number, center: Vec } }
+
+export type Flocking = {
+    perception: number,
+    separation_distance: number,
+    field_of_view: number,
+    separation: number,
+    alignment: number,
+    cohesion: number,
+    max_speed: number,
+}
+
+export type PointVortices = {
+    softening: number,
+}
+
+export type LennardJones = {
+    epsilon: number,
+    sigma: number,
+    cutoff: number,
+}
+
+export type Thermostat =
+    | { Berendsen: { temperature: number, time_constant: number } }
+    | { Langevin: { temperature: number, friction: number } }
+
+export type Gas = {
+    smoothing_length: number,
+    density: number,
+    internal_energy: number,
+}
+
+export type EquationOfState =
+    | { IdealGas: { gamma: number } }
+    | { Polytropic: { constant: number, gamma: number } }
+
+export type Sph = {
+    equation_of_state: EquationOfState,
+    alpha: number,
+    beta: number,
+    neighbours: number,
+}
+
+export type Integrator = "SemiImplicitEuler" | "Boris"